@@ -0,0 +1,211 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+//! Integration with [actix-web](https://actix.rs), which is available
+//! if the `actix` feature of this library is enabled.
+//!
+//! Handlers receive validated tokens through the `ValidToken` and
+//! `Claims` extractors, which validate the bearer token of a request
+//! using the `Validator` registered as application data:
+//!
+//! ```rust,no_run
+//! # use alcoholic_jwt::actix::Claims;
+//! # use alcoholic_jwt::{Validation, Validator};
+//! # use actix_web::{web, App};
+//! # use serde_derive::Deserialize;
+//! #[derive(Deserialize)]
+//! struct User {
+//!     sub: String,
+//! }
+//!
+//! async fn profile(user: Claims<User>) -> String {
+//!     format!("Hello, {}!", user.0.sub)
+//! }
+//!
+//! # let validator = Validator::new(vec![Validation::NotExpired]);
+//! let app = App::new()
+//!     .app_data(web::Data::new(validator))
+//!     .route("/profile", web::get().to(profile));
+//! ```
+//!
+//! Alternatively, the `RequireToken` middleware rejects all requests
+//! without a valid token before they reach a handler. Extractors reuse
+//! the token validated by the middleware.
+//!
+//! Failed validations are rejected with `401 Unauthorized` and a
+//! `WWW-Authenticate` header as specified in RFC 6750.
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderMap, AUTHORIZATION, WWW_AUTHENTICATE};
+use actix_web::http::StatusCode;
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest, HttpResponse, ResponseError};
+use serde::de::DeserializeOwned;
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::auth::authenticate;
+use crate::{AuthError, ValidJWT, Validator};
+
+type LocalBoxFuture<T> = Pin<Box<dyn Future<Output = T>>>;
+
+impl ResponseError for AuthError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.status()).unwrap_or(StatusCode::UNAUTHORIZED)
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        if let Some(challenge) = self.www_authenticate() {
+            response.insert_header((WWW_AUTHENTICATE, challenge));
+        }
+
+        response.finish()
+    }
+}
+
+/// Return the `Authorization` header of a request.
+fn authorization(headers: &HeaderMap) -> Option<&str> {
+    headers.get(AUTHORIZATION).and_then(|value| value.to_str().ok())
+}
+
+/// Return the token validated by the middleware, or validate the
+/// token of the request using the application's validator.
+async fn request_jwt(req: HttpRequest) -> Result<ValidJWT, AuthError> {
+    if let Some(jwt) = req.extensions().get::<ValidJWT>() {
+        return Ok(jwt.clone());
+    }
+
+    let validator = req.app_data::<web::Data<Validator>>()
+        .cloned()
+        .ok_or(AuthError::MissingValidator)?;
+
+    authenticate(&validator, authorization(req.headers()), &[]).await
+}
+
+/// Extractor for the validated token of a request.
+#[derive(Clone, Debug)]
+pub struct ValidToken(pub ValidJWT);
+
+impl FromRequest for ValidToken {
+    type Error = AuthError;
+    type Future = LocalBoxFuture<Result<ValidToken, AuthError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move { request_jwt(req).await.map(ValidToken) })
+    }
+}
+
+/// Extractor for the claims of the validated token of a request,
+/// deserialised into the specified type.
+///
+/// Tokens whose claims can not be deserialised are rejected like
+/// invalid tokens.
+#[derive(Clone, Debug)]
+pub struct Claims<T>(pub T);
+
+impl<T: DeserializeOwned + 'static> FromRequest for Claims<T> {
+    type Error = AuthError;
+    type Future = LocalBoxFuture<Result<Claims<T>, AuthError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move {
+            let jwt = request_jwt(req).await?;
+            serde_json::from_value(jwt.claims)
+                .map(Claims)
+                .map_err(|err| AuthError::InvalidToken(err.into()))
+        })
+    }
+}
+
+/// Middleware that rejects requests without a valid bearer token and
+/// makes the validated token available to the `ValidToken` and
+/// `Claims` extractors.
+///
+/// ```rust,no_run
+/// # use alcoholic_jwt::actix::RequireToken;
+/// # use alcoholic_jwt::{Validation, Validator};
+/// # use actix_web::{web, App};
+/// # use std::sync::Arc;
+/// # let validator = Validator::new(vec![Validation::NotExpired]);
+/// let app = App::new()
+///     .wrap(RequireToken::new(Arc::new(validator)))
+///     .route("/", web::get().to(|| async { "authenticated" }));
+/// ```
+#[derive(Clone)]
+pub struct RequireToken {
+    validator: Arc<Validator>,
+}
+
+impl RequireToken {
+    /// Create a middleware that validates tokens using the specified
+    /// validator.
+    pub fn new(validator: Arc<Validator>) -> RequireToken {
+        RequireToken { validator }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequireToken
+where S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+      B: 'static {
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Transform = RequireTokenService<S>;
+    type InitError = ();
+    type Future = Ready<Result<RequireTokenService<S>, ()>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequireTokenService {
+            service: Rc::new(service),
+            validator: self.validator.clone(),
+        }))
+    }
+}
+
+/// Service constructed by the `RequireToken` middleware.
+pub struct RequireTokenService<S> {
+    service: Rc<S>,
+    validator: Arc<Validator>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequireTokenService<S>
+where S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+      B: 'static {
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let validator = self.validator.clone();
+
+        Box::pin(async move {
+            match authenticate(&validator, authorization(req.headers()), &[]).await {
+                Ok(jwt) => {
+                    req.extensions_mut().insert(jwt);
+                    service.call(req).await.map(ServiceResponse::map_into_left_body)
+                },
+                Err(err) => Ok(req.error_response(err).map_into_right_body()),
+            }
+        })
+    }
+}
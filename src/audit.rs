@@ -0,0 +1,106 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+//! Defines the `AuditHook` trait, through which validators report
+//! every decision about a token, e.g. for streaming them to a SIEM.
+
+use serde_json::Value;
+
+use crate::{dangerous_decode_unverified, JWTResult, ValidJWT, ValidationError};
+
+/// Decision of a validator about a token.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuditDecision {
+    /// The token passed all validations.
+    Accepted,
+
+    /// The token was rejected, see `AuditEvent::reason`.
+    Rejected,
+}
+
+/// Structured record of a single decision about a token.
+///
+/// For accepted tokens, the claims in the event have been validated.
+/// For rejected tokens they are taken from the token without
+/// verifying it (if it can be decoded at all) and must not be trusted,
+/// even though they are useful for investigating rejections.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct AuditEvent<'a> {
+    /// Whether the token was accepted or rejected.
+    pub decision: AuditDecision,
+
+    /// Reason for rejecting the token.
+    pub reason: Option<&'a ValidationError>,
+
+    /// The "kid" header of the token.
+    pub kid: Option<&'a str>,
+
+    /// The "iss" claim of the token.
+    pub iss: Option<&'a str>,
+
+    /// The "sub" claim of the token.
+    pub sub: Option<&'a str>,
+
+    /// The "jti" claim of the token.
+    pub jti: Option<&'a str>,
+}
+
+/// Receiver of audit events, see `Validator::audit_hook`.
+///
+/// The hook is called exactly once for every token passed to one of
+/// the validation functions of a `Validator`, after the decision about
+/// the token has been made and before it is returned to the caller.
+/// This includes tokens served from the token cache. The hook is
+/// called on the validation path and should not block, e.g. by
+/// sending events to a channel instead of writing them out directly.
+///
+/// Closures taking an `&AuditEvent` implement this trait.
+pub trait AuditHook: Send + Sync {
+    /// Record a decision about a token.
+    fn record(&self, event: &AuditEvent);
+}
+
+impl<F> AuditHook for F where F: Fn(&AuditEvent) + Send + Sync {
+    fn record(&self, event: &AuditEvent) {
+        self(event)
+    }
+}
+
+/// Construct the audit event for a validation result and pass it to
+/// the hook.
+pub(crate) fn audit(hook: &dyn AuditHook, token: &str, result: &JWTResult<ValidJWT>) {
+    let unverified;
+    let (headers, claims, reason) = match *result {
+        Ok(ref jwt) => (&jwt.headers, &jwt.claims, None),
+        Err(ref err) => {
+            unverified = dangerous_decode_unverified(token).ok();
+            match unverified {
+                Some(ref jwt) => (&jwt.headers, &jwt.claims, Some(err)),
+                None => (&Value::Null, &Value::Null, Some(err)),
+            }
+        },
+    };
+
+    hook.record(&AuditEvent {
+        decision: if reason.is_none() { AuditDecision::Accepted } else { AuditDecision::Rejected },
+        reason,
+        kid: headers.get("kid").and_then(Value::as_str),
+        iss: claims.get("iss").and_then(Value::as_str),
+        sub: claims.get("sub").and_then(Value::as_str),
+        jti: claims.get("jti").and_then(Value::as_str),
+    });
+}
@@ -0,0 +1,144 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+//! Implements the authentication of requests with bearer tokens
+//! shared by the integrations with web frameworks.
+
+use std::fmt;
+
+use crate::ValidationError;
+
+#[cfg(any(feature = "actix", feature = "tower", feature = "rocket", feature = "warp", feature = "tonic"))]
+use crate::{bearer_token, token_scopes, ClaimFailure, ValidJWT, Validator};
+
+#[cfg(any(feature = "actix", feature = "tower", feature = "rocket", feature = "warp", feature = "tonic"))]
+use serde_json::Value;
+
+/// Reasons for rejecting a request authenticated with a bearer token,
+/// as returned by the integrations with web frameworks.
+///
+/// Each reason maps to an HTTP status and a `WWW-Authenticate` header
+/// as specified in [RFC 6750, section
+/// 3](https://tools.ietf.org/html/rfc6750#section-3).
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum AuthError {
+    /// The request does not contain a bearer token.
+    MissingToken,
+
+    /// No `Validator` has been configured for the request handler.
+    MissingValidator,
+
+    /// The token is not valid.
+    InvalidToken(ValidationError),
+
+    /// The token is valid, but does not grant the specified scope
+    /// (which is empty if it is not known, e.g. for tokens without
+    /// any scopes that failed `Validation::HasScope`).
+    InsufficientScope(String),
+}
+
+impl AuthError {
+    /// Return the HTTP status code of responses to rejected requests.
+    pub fn status(&self) -> u16 {
+        match *self {
+            AuthError::MissingToken | AuthError::InvalidToken(_) => 401,
+            AuthError::InsufficientScope(_) => 403,
+            AuthError::MissingValidator => 500,
+        }
+    }
+
+    /// Return the value of the `WWW-Authenticate` header of responses
+    /// to rejected requests, if any.
+    pub fn www_authenticate(&self) -> Option<String> {
+        match *self {
+            AuthError::MissingToken => Some("Bearer".into()),
+            AuthError::InvalidToken(_) => Some("Bearer error=\"invalid_token\"".into()),
+            AuthError::InsufficientScope(ref scope) if scope.is_empty() => {
+                Some("Bearer error=\"insufficient_scope\"".into())
+            },
+            AuthError::InsufficientScope(ref scope) => {
+                Some(format!("Bearer error=\"insufficient_scope\", scope=\"{}\"", scope))
+            },
+            AuthError::MissingValidator => None,
+        }
+    }
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AuthError::MissingToken => f.write_str("request does not contain a bearer token"),
+            AuthError::MissingValidator => f.write_str("no token validator has been configured"),
+            AuthError::InvalidToken(ref err) => write!(f, "invalid bearer token: {}", err),
+            AuthError::InsufficientScope(ref scope) => write!(f, "token does not grant scope '{}'", scope),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            AuthError::InvalidToken(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Validate the bearer token contained in the specified
+/// `Authorization` header, and check that it grants all of the
+/// specified scopes.
+#[cfg(any(feature = "actix", feature = "tower", feature = "rocket", feature = "warp", feature = "tonic"))]
+pub(crate) async fn authenticate(validator: &Validator,
+                                 authorization: Option<&str>,
+                                 scopes: &[String]) -> Result<ValidJWT, AuthError> {
+    let token = authorization.and_then(bearer_token).ok_or(AuthError::MissingToken)?;
+    let jwt = validator.validate_async(token).await.map_err(rejection)?;
+    check_scopes(&jwt, scopes)?;
+    Ok(jwt)
+}
+
+/// Construct the rejection for a token that failed validation.
+///
+/// Tokens that only failed `Validation::HasScope` are valid, but do not
+/// grant the required scope.
+#[cfg(any(feature = "actix", feature = "tower", feature = "rocket", feature = "warp", feature = "tonic"))]
+pub(crate) fn rejection(err: ValidationError) -> AuthError {
+    let scope = match err {
+        ValidationError::InvalidClaims(ref failures) if failures.iter().all(|f| f.claim() == Some("scope")) => {
+            match failures.first() {
+                Some(ClaimFailure::Mismatch { expected: Value::String(scope), .. }) => Some(scope.clone()),
+                Some(_) => Some(String::new()),
+                None => None,
+            }
+        },
+        _ => None,
+    };
+
+    match scope {
+        Some(scope) => AuthError::InsufficientScope(scope),
+        None => AuthError::InvalidToken(err),
+    }
+}
+
+/// Check that a validated token grants all of the specified scopes.
+#[cfg(any(feature = "actix", feature = "tower", feature = "rocket", feature = "warp", feature = "tonic"))]
+pub(crate) fn check_scopes(jwt: &ValidJWT, scopes: &[String]) -> Result<(), AuthError> {
+    match scopes.iter().find(|scope| !token_scopes(&jwt.claims).any(|s| s == scope.as_str())) {
+        Some(scope) => Err(AuthError::InsufficientScope(scope.clone())),
+        None => Ok(()),
+    }
+}
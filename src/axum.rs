@@ -0,0 +1,125 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+//! Integration with [axum](https://github.com/tokio-rs/axum), which
+//! is available if the `axum` feature of this library is enabled.
+//!
+//! The `RequireToken` layer (see the `tower` module) rejects requests
+//! without a valid bearer token, optionally requiring specific scopes,
+//! and makes the validated token available to the `ValidToken` and
+//! `Claims` extractors. Layers can be applied to a whole router or to
+//! individual routes:
+//!
+//! ```rust,no_run
+//! # use alcoholic_jwt::axum::{Claims, RequireToken};
+//! # use alcoholic_jwt::{Validation, Validator};
+//! # use axum::{routing::get, Router};
+//! # use serde_derive::Deserialize;
+//! # use std::sync::Arc;
+//! #[derive(Deserialize)]
+//! struct User {
+//!     sub: String,
+//! }
+//!
+//! async fn profile(user: Claims<User>) -> String {
+//!     format!("Hello, {}!", user.0.sub)
+//! }
+//!
+//! # let validator = Arc::new(Validator::new(vec![Validation::NotExpired]));
+//! let app: Router = Router::new()
+//!     .route("/profile", get(profile))
+//!     .route("/admin", get(profile).layer(RequireToken::new(validator.clone()).scope("admin")))
+//!     .layer(RequireToken::new(validator));
+//! ```
+//!
+//! Without the layer, the extractors validate tokens themselves using
+//! a validator added as an extension (`Extension(Arc<Validator>)`).
+//!
+//! Requests rejected by the extractors receive a response with the
+//! status and `WWW-Authenticate` header described by `AuthError`.
+
+use ::axum::extract::FromRequestParts;
+use ::axum::http::header::{HeaderMap, AUTHORIZATION, WWW_AUTHENTICATE};
+use ::axum::http::request::Parts;
+use ::axum::http::StatusCode;
+use ::axum::response::{IntoResponse, Response};
+use serde::de::DeserializeOwned;
+use std::sync::Arc;
+
+use crate::auth::authenticate;
+use crate::{AuthError, ValidJWT, Validator};
+
+pub use crate::tower::{RequireToken, RequireTokenService};
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status()).unwrap_or(StatusCode::UNAUTHORIZED);
+        match self.www_authenticate() {
+            Some(challenge) => (status, [(WWW_AUTHENTICATE, challenge)]).into_response(),
+            None => status.into_response(),
+        }
+    }
+}
+
+/// Return the `Authorization` header of a request.
+fn authorization(headers: &HeaderMap) -> Option<&str> {
+    headers.get(AUTHORIZATION).and_then(|value| value.to_str().ok())
+}
+
+/// Return the token validated by the layer, or validate the token of
+/// the request using the validator added as an extension.
+async fn request_jwt(parts: &Parts) -> Result<ValidJWT, AuthError> {
+    if let Some(jwt) = parts.extensions.get::<ValidJWT>() {
+        return Ok(jwt.clone());
+    }
+
+    let validator = parts.extensions.get::<Arc<Validator>>()
+        .cloned()
+        .ok_or(AuthError::MissingValidator)?;
+
+    authenticate(&validator, authorization(&parts.headers), &[]).await
+}
+
+/// Extractor for the validated token of a request.
+#[derive(Clone, Debug)]
+pub struct ValidToken(pub ValidJWT);
+
+impl<S: Send + Sync> FromRequestParts<S> for ValidToken {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<ValidToken, AuthError> {
+        request_jwt(parts).await.map(ValidToken)
+    }
+}
+
+/// Extractor for the claims of the validated token of a request,
+/// deserialised into the specified type.
+///
+/// Tokens whose claims can not be deserialised are rejected like
+/// invalid tokens.
+#[derive(Clone, Debug)]
+pub struct Claims<T>(pub T);
+
+impl<S: Send + Sync, T: DeserializeOwned> FromRequestParts<S> for Claims<T> {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Claims<T>, AuthError> {
+        let jwt = request_jwt(parts).await?;
+        serde_json::from_value(jwt.claims)
+            .map(Claims)
+            .map_err(|err| AuthError::InvalidToken(err.into()))
+    }
+}
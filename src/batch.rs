@@ -0,0 +1,51 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Implements validation of large numbers of tokens against the same
+//! key set.
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::{JWKS, JWTResult, KeyProvider, ValidJWT, Validation, token_limits, validate_against, with_token_kid};
+
+/// Validate several tokens using keys from the same key set.
+///
+/// Keys are selected based on the `kid` header of each token, see
+/// `KeyProvider::key_for`. Each public key is only constructed once
+/// for the entire batch (and in fact for the lifetime of the key
+/// set).
+///
+/// If the `rayon` feature is enabled, the tokens are validated in
+/// parallel on the global rayon thread pool.
+///
+/// The results are returned in the order of the tokens.
+pub fn validate_batch(tokens: &[&str],
+                      jwks: &JWKS,
+                      validations: &[Validation]) -> Vec<JWTResult<ValidJWT>> {
+    #[cfg(feature = "rayon")]
+    let tokens = tokens.par_iter();
+
+    #[cfg(not(feature = "rayon"))]
+    let tokens = tokens.iter();
+
+    tokens.map(|token| validate_one(token, jwks, validations)).collect()
+}
+
+/// Validate a single token of a batch.
+fn validate_one(token: &str, jwks: &JWKS, validations: &[Validation]) -> JWTResult<ValidJWT> {
+    let jwk = with_token_kid(token, &token_limits(validations), |kid| jwks.key_for(kid))?;
+    validate_against(token, Some(jwk.as_ref()), validations)
+}
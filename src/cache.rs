@@ -0,0 +1,258 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Defines the `CacheBackend` trait, which allows several instances
+//! of an application to share fetched key sets.
+//!
+//! A reference implementation backed by Redis is available if the
+//! `redis` feature is enabled.
+
+use std::time::SystemTime;
+
+use crate::provider::BoxFuture;
+use crate::JWTResult;
+
+/// A key set document stored in a cache backend, together with the
+/// metadata required for refreshing it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CacheEntry {
+    /// The JWKS document as returned by the server.
+    pub jwks: String,
+
+    /// Point in time at which the key set becomes stale.
+    pub expires_at: SystemTime,
+
+    /// Value of the `ETag` header of the response, if any.
+    pub etag: Option<String>,
+
+    /// Value of the `Last-Modified` header of the response, if any.
+    pub last_modified: Option<String>,
+}
+
+/// External cache for key sets that can be shared between several
+/// `KeyStore` instances, for example across a fleet of servers.
+///
+/// A key store whose cached keys are stale first consults its backend
+/// and only fetches the key set from its URL if the backend does not
+/// hold a fresh copy. Fetched key sets are written to the backend.
+///
+/// Errors returned by backends are not fatal: the key store falls
+/// back to fetching the key set itself.
+pub trait CacheBackend: Send + Sync {
+    /// Load the entry stored for the key set at the specified URL.
+    fn load(&self, url: &str) -> JWTResult<Option<CacheEntry>>;
+
+    /// Store an entry for the key set at the specified URL.
+    fn store(&self, url: &str, entry: &CacheEntry) -> JWTResult<()>;
+
+    /// Asynchronous equivalent of `CacheBackend::load`, used by the
+    /// asynchronous key store functions.
+    ///
+    /// The default implementation calls `CacheBackend::load`.
+    fn load_async<'a>(&'a self, url: &'a str) -> BoxFuture<'a, JWTResult<Option<CacheEntry>>> {
+        Box::pin(std::future::ready(self.load(url)))
+    }
+
+    /// Asynchronous equivalent of `CacheBackend::store`.
+    ///
+    /// The default implementation calls `CacheBackend::store`.
+    fn store_async<'a>(&'a self, url: &'a str, entry: &'a CacheEntry) -> BoxFuture<'a, JWTResult<()>> {
+        Box::pin(std::future::ready(self.store(url, entry)))
+    }
+}
+
+#[cfg(feature = "redis")]
+pub use self::redis_cache::RedisCache;
+
+#[cfg(feature = "redis")]
+mod redis_cache {
+    use redis::Commands;
+    use std::sync::Mutex;
+    use std::time::{Duration, SystemTime};
+
+    use super::{CacheBackend, CacheEntry};
+    use crate::{JWTResult, ValidationError};
+
+    #[cfg(feature = "async")]
+    use crate::provider::BoxFuture;
+
+    /// Cache backend that stores key sets in Redis.
+    ///
+    /// Entries are stored as JSON strings under the configured key
+    /// prefix followed by the JWKS URL, and expire in Redis once they
+    /// become stale.
+    ///
+    /// Connections are reused between requests. If the `async`
+    /// feature is enabled, the asynchronous key store functions use a
+    /// non-blocking connection that is re-established automatically.
+    pub struct RedisCache {
+        client: redis::Client,
+        prefix: String,
+        connect_timeout: Duration,
+        response_timeout: Duration,
+        connection: Mutex<Option<redis::Connection>>,
+
+        #[cfg(feature = "async")]
+        manager: tokio::sync::OnceCell<redis::aio::ConnectionManager>,
+    }
+
+    impl RedisCache {
+        /// Create a backend for the Redis server at the specified URL,
+        /// e.g. `redis://127.0.0.1/`. No connection is made until the
+        /// backend is first used.
+        pub fn new(url: &str) -> JWTResult<RedisCache> {
+            Ok(RedisCache {
+                client: redis::Client::open(url)?,
+                prefix: "alcoholic_jwt:jwks:".into(),
+                connect_timeout: Duration::from_secs(1),
+                response_timeout: Duration::from_secs(1),
+                connection: Mutex::new(None),
+
+                #[cfg(feature = "async")]
+                manager: tokio::sync::OnceCell::new(),
+            })
+        }
+
+        /// Set the prefix of the Redis keys used for storing entries.
+        /// Defaults to `alcoholic_jwt:jwks:`.
+        pub fn prefix(mut self, prefix: &str) -> RedisCache {
+            self.prefix = prefix.into();
+            self
+        }
+
+        /// Set the maximum time to wait for a connection to the Redis
+        /// server. Defaults to one second.
+        pub fn connect_timeout(mut self, timeout: Duration) -> RedisCache {
+            self.connect_timeout = timeout;
+            self
+        }
+
+        /// Set the maximum time to wait for a response from the Redis
+        /// server. Defaults to one second.
+        pub fn response_timeout(mut self, timeout: Duration) -> RedisCache {
+            self.response_timeout = timeout;
+            self
+        }
+
+        /// Return the Redis key for the key set at the specified URL.
+        fn key(&self, url: &str) -> String {
+            format!("{}{}", self.prefix, url)
+        }
+
+        /// Run a command on the blocking connection, establishing it
+        /// first if necessary. Connections that fail are discarded.
+        fn with_connection<T, F>(&self, command: F) -> JWTResult<T>
+        where F: FnOnce(&mut redis::Connection) -> redis::RedisResult<T> {
+            let idle = self.connection.lock().unwrap().take();
+            let mut connection = match idle {
+                Some(connection) => connection,
+                None => {
+                    let connection = self.client.get_connection_with_timeout(self.connect_timeout)?;
+                    connection.set_read_timeout(Some(self.response_timeout))?;
+                    connection.set_write_timeout(Some(self.response_timeout))?;
+                    connection
+                }
+            };
+
+            let result = command(&mut connection)?;
+            self.connection.lock().unwrap().get_or_insert(connection);
+            Ok(result)
+        }
+
+        /// Return the asynchronous connection, establishing it on
+        /// first use.
+        #[cfg(feature = "async")]
+        async fn manager(&self) -> JWTResult<redis::aio::ConnectionManager> {
+            let manager = self.manager.get_or_try_init(|| {
+                // Key stores fall back to fetching key sets themselves,
+                // so an unavailable server is not worth waiting for.
+                let config = redis::aio::ConnectionManagerConfig::new()
+                    .set_number_of_retries(0)
+                    .set_connection_timeout(Some(self.connect_timeout))
+                    .set_response_timeout(Some(self.response_timeout));
+                redis::aio::ConnectionManager::new_with_config(self.client.clone(), config)
+            }).await?;
+
+            Ok(manager.clone())
+        }
+    }
+
+    /// Return the number of seconds until the entry becomes stale, or
+    /// `None` if it should not be stored.
+    fn ttl(entry: &CacheEntry) -> Option<u64> {
+        match entry.expires_at.duration_since(SystemTime::now()) {
+            Ok(ttl) if ttl.as_secs() > 0 => Some(ttl.as_secs()),
+
+            // Stale entries are of no use to other instances
+            _ => None,
+        }
+    }
+
+    fn parse(value: Option<String>) -> JWTResult<Option<CacheEntry>> {
+        match value {
+            Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+            None => Ok(None),
+        }
+    }
+
+    impl CacheBackend for RedisCache {
+        fn load(&self, url: &str) -> JWTResult<Option<CacheEntry>> {
+            let key = self.key(url);
+            parse(self.with_connection(|connection| connection.get(key))?)
+        }
+
+        fn store(&self, url: &str, entry: &CacheEntry) -> JWTResult<()> {
+            let ttl = match ttl(entry) {
+                Some(ttl) => ttl,
+                None => return Ok(()),
+            };
+
+            let json = serde_json::to_string(entry)?;
+            let key = self.key(url);
+            self.with_connection(|connection| connection.set_ex::<_, _, ()>(key, json, ttl))
+        }
+
+        #[cfg(feature = "async")]
+        fn load_async<'a>(&'a self, url: &'a str) -> BoxFuture<'a, JWTResult<Option<CacheEntry>>> {
+            Box::pin(async move {
+                use redis::AsyncCommands;
+
+                let mut manager = self.manager().await?;
+                parse(manager.get(self.key(url)).await?)
+            })
+        }
+
+        #[cfg(feature = "async")]
+        fn store_async<'a>(&'a self, url: &'a str, entry: &'a CacheEntry) -> BoxFuture<'a, JWTResult<()>> {
+            Box::pin(async move {
+                use redis::AsyncCommands;
+
+                let ttl = match ttl(entry) {
+                    Some(ttl) => ttl,
+                    None => return Ok(()),
+                };
+
+                let json = serde_json::to_string(entry)?;
+                let mut manager = self.manager().await?;
+                manager.set_ex::<_, _, ()>(self.key(url), json, ttl).await?;
+                Ok(())
+            })
+        }
+    }
+
+    impl From<redis::RedisError> for ValidationError {
+        fn from(err: redis::RedisError) -> Self { ValidationError::Cache(Box::new(err)) }
+    }
+}
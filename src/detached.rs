@@ -0,0 +1,100 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Implements verification of JWS with detached and unencoded
+//! payloads as specified in [RFC
+//! 7797](https://tools.ietf.org/html/rfc7797).
+//!
+//! Detached signatures are commonly used for signing HTTP message
+//! bodies (e.g. webhooks), in which case the signature is transmitted
+//! as a JWS with an empty payload component and the payload is the
+//! body of the message.
+
+use serde_json::{Map, Value};
+
+use crate::{
+    deserialize_part, token_limits, token_parts, validate_claim_set,
+    verify_signed_data, ClaimFailure, JWK, JWTResult, ValidJWT, Validation, ValidationError,
+};
+
+/// Verify a JWS with a detached payload, which is supplied separately
+/// by the caller, and optionally apply claim validations.
+///
+/// The JWS must be in compact serialisation with an empty payload
+/// component (`header..signature`). If its "b64"-header is `false`,
+/// the payload was signed as is, otherwise it was signed in its
+/// base64url-encoded form. As required by RFC 7797, a "b64"-header
+/// must be listed in the "crit"-header.
+///
+/// If the payload is a JSON object, it is returned as the claims of
+/// the token and validated like the claims of any other token.
+/// Otherwise the claims are `null` and validations are applied to an
+/// empty claim set, i.e. validations requiring claims fail.
+pub fn validate_detached(jws: &str,
+                         payload: &[u8],
+                         jwk: &JWK,
+                         validations: Vec<Validation>) -> JWTResult<ValidJWT> {
+    token_limits(&validations).check_token(jws)?;
+
+    let [header_b64, detached, sig_b64] = token_parts(jws)?;
+    if !detached.is_empty() {
+        return Err(ValidationError::InvalidComponents);
+    }
+
+    let headers: Value = deserialize_part(header_b64)?;
+    let encoded = match headers.get("b64") {
+        None => true,
+        Some(Value::Bool(b64)) => *b64,
+        Some(_) => return Err(ValidationError::InvalidComponents),
+    };
+
+    if headers.get("b64").is_some() && !is_critical(&headers, "b64") {
+        let failure = ClaimFailure::invalid("b64", "must be listed as critical");
+        return Err(ValidationError::InvalidClaims(vec![failure]));
+    }
+
+    let signed_payload = if encoded {
+        base64::encode_config(payload, base64::URL_SAFE_NO_PAD).into_bytes()
+    } else {
+        payload.to_vec()
+    };
+
+    let key = jwk.public_key()?;
+    verify_signed_data(&[header_b64.as_bytes(), b".", &signed_payload], sig_b64, key)?;
+
+    let claims = match serde_json::from_slice(payload) {
+        Ok(Value::Object(claims)) => Value::Object(claims),
+        _ => Value::Null,
+    };
+
+    // The "b64"-header is understood by this function.
+    let mut validations = validations;
+    validations.push(Validation::critical_header("b64", Value::is_boolean));
+
+    // Other payloads are validated as an empty claim set.
+    match claims {
+        Value::Null => validate_claim_set(&headers, &Value::Object(Map::new()), &validations)?,
+        ref claims => validate_claim_set(&headers, claims, &validations)?,
+    }
+
+    Ok(ValidJWT { headers, claims })
+}
+
+/// Check whether a header parameter is listed in the "crit"-header.
+fn is_critical(headers: &Value, name: &str) -> bool {
+    headers.get("crit")
+        .and_then(Value::as_array)
+        .is_some_and(|crit| crit.iter().any(|n| n == name))
+}
@@ -0,0 +1,132 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Implements validation of DPoP proofs as specified in [RFC
+//! 9449](https://tools.ietf.org/html/rfc9449).
+//!
+//! A DPoP proof is a JWT signed with a key held by the client, which
+//! is embedded in the header of the proof. Access tokens bound to that
+//! key contain its thumbprint in the "jkt" member of their
+//! "cnf"-claim, which can be validated using `Validation::DPoPBinding`.
+//!
+//! Like the rest of this library, only proofs signed using `RS256` are
+//! supported.
+
+use serde::Deserialize;
+use serde_json::Value;
+use std::time::Duration;
+
+use crate::{deserialize_part, token_limits, validate, JWK, JWTResult, TimeContext, ValidJWT, Validation, ValidationError};
+
+/// Default maximum age of DPoP proofs, based on their "iat"-claim.
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(300);
+
+/// Representation of a validated DPoP proof.
+#[derive(Clone, Debug)]
+pub struct DPoPProof {
+    /// The validated proof token.
+    pub jwt: ValidJWT,
+
+    /// The public key embedded in the proof.
+    pub jwk: JWK,
+
+    /// The JWK thumbprint of the embedded key, which access tokens
+    /// bound to the key contain in their "cnf.jkt"-claim.
+    pub jkt: String,
+}
+
+/// Compute the value of the "ath"-claim of a DPoP proof for the
+/// specified access token, i.e. its base64url-encoded SHA-256 hash.
+pub fn access_token_hash(access_token: &str) -> String {
+    base64::encode_config(&openssl::sha::sha256(access_token.as_bytes()), base64::URL_SAFE_NO_PAD)
+}
+
+/// Strip the query and fragment components from a URL, which are
+/// ignored when comparing the "htu"-claim of a DPoP proof.
+fn strip_query(url: &str) -> &str {
+    url.split(['?', '#']).next().unwrap_or(url)
+}
+
+/// Validate a DPoP proof for an HTTP request with the specified method
+/// and URL.
+///
+/// The proof must have the type `dpop+jwt`, be signed by the public
+/// key embedded in its header, specify the request method and URL in
+/// its "htm" and "htu" claims, contain a "jti"-claim and have
+/// been issued within the last five minutes, but not later than the
+/// current time plus the leeway. If an access token is specified, the
+/// "ath"-claim of the proof must contain its hash.
+///
+/// Additional validations, e.g. `Validation::NoReplay` for rejecting
+/// reused proofs or `Validation::IssuedAtMaxAge` for overriding the
+/// maximum age, can be supplied as well.
+pub fn validate_dpop_proof(proof: &str,
+                           method: &str,
+                           url: &str,
+                           access_token: Option<&str>,
+                           validations: Vec<Validation>) -> JWTResult<DPoPProof> {
+    token_limits(&validations).check_token(proof)?;
+    let header: Value = deserialize_part(proof.split('.').next().unwrap_or(""))?;
+
+    // Only RS256 signatures can be verified by this library.
+    if header.get("alg").and_then(Value::as_str) != Some("RS256") {
+        return Err(ValidationError::InvalidSignature);
+    }
+
+    let jwk = match header.get("jwk") {
+        Some(jwk) => JWK::deserialize(jwk).map_err(|_| ValidationError::InvalidJWK)?,
+        None => return Err(ValidationError::InvalidJWK),
+    };
+
+    // The embedded key must not contain private key material.
+    if ["d", "p", "q", "dp", "dq", "qi"].iter().any(|member| jwk.extra.contains_key(*member)) {
+        return Err(ValidationError::InvalidJWK);
+    }
+
+    let htu = strip_query(url).to_string();
+    let mut proof_validations = vec![
+        Validation::TokenType("dpop+jwt".into()),
+        Validation::ClaimEquals("htm".into(), method.into()),
+        Validation::custom(move |claims| {
+            claims.get("htu").and_then(Value::as_str).map(strip_query) == Some(htu.as_str())
+        }),
+        Validation::ClaimsPresent(vec!["jti".into()]),
+    ];
+
+    // Proofs dated in the future could have been generated in advance,
+    // see RFC 9449, section 11.1.
+    let time = TimeContext::new(&validations);
+    let latest = time.now.saturating_add(time.leeway).as_secs();
+    proof_validations.push(Validation::custom(move |claims| {
+        claims.get("iat").and_then(Value::as_u64).is_some_and(|iat| iat <= latest)
+    }));
+
+    // The default maximum age only applies if the caller does not
+    // specify one.
+    if !validations.iter().any(|v| matches!(v, Validation::IssuedAtMaxAge(_))) {
+        proof_validations.push(Validation::IssuedAtMaxAge(DEFAULT_MAX_AGE));
+    }
+
+    if let Some(access_token) = access_token {
+        proof_validations.push(Validation::ClaimEquals("ath".into(), access_token_hash(access_token).into()));
+    }
+
+    proof_validations.extend(validations);
+
+    let jwt = validate(proof, &jwk, proof_validations)?;
+    let jkt = jwk.thumbprint();
+
+    Ok(DPoPProof { jwt, jwk, jkt })
+}
@@ -0,0 +1,154 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Retrieval of key sets over HTTP.
+//!
+//! The functions in this module are only available if one of the
+//! HTTP-related features of this library is enabled.
+
+use reqwest::header::{HeaderMap, ACCEPT, CONTENT_TYPE};
+use reqwest::StatusCode;
+
+use crate::{JWKS, JWTResult, Limits, ValidationError};
+
+/// Media types accepted for JWKS documents. Many providers serve
+/// their key sets as plain `application/json`.
+pub(crate) const ACCEPT_JWKS: &str = "application/jwk-set+json, application/json";
+
+/// Check whether the value of a `Content-Type` header denotes a JSON
+/// document, i.e. `application/json` or any `+json` media type.
+fn is_json(content_type: &str) -> bool {
+    let media_type = content_type.split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+
+    media_type == "application/json" || media_type.ends_with("+json")
+}
+
+/// Check the status and content type of an HTTP response containing
+/// a JSON document before attempting to deserialise it.
+///
+/// Responses without a `Content-Type` header are accepted.
+pub(crate) fn check_response(status: StatusCode, headers: &HeaderMap) -> JWTResult<()> {
+    if status != StatusCode::OK {
+        return Err(ValidationError::UnexpectedStatus(status.as_u16()));
+    }
+
+    if let Some(content_type) = headers.get(CONTENT_TYPE) {
+        let content_type = content_type.to_str().unwrap_or("");
+        if !is_json(content_type) {
+            return Err(ValidationError::UnexpectedContentType(content_type.into()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Check the announced length of an HTTP response body against the
+/// specified maximum size.
+fn check_length(content_length: Option<u64>, max: usize) -> JWTResult<()> {
+    match content_length {
+        Some(len) if len > max as u64 => Err(ValidationError::LimitExceeded("max_jwks_size")),
+        _ => Ok(()),
+    }
+}
+
+/// Read the body of a blocking HTTP response, which must not exceed
+/// the specified size. Oversized bodies are not read completely.
+#[cfg(feature = "fetch")]
+pub(crate) fn read_body(response: reqwest::blocking::Response, max: usize) -> JWTResult<Vec<u8>> {
+    use std::io::Read;
+
+    check_length(response.content_length(), max)?;
+
+    let mut body = Vec::new();
+    response.take(max as u64 + 1).read_to_end(&mut body)?;
+    if body.len() > max {
+        return Err(ValidationError::LimitExceeded("max_jwks_size"));
+    }
+
+    Ok(body)
+}
+
+/// Read the body of an asynchronous HTTP response, see `read_body`.
+#[cfg(feature = "async")]
+pub(crate) async fn read_body_async(mut response: reqwest::Response, max: usize) -> JWTResult<Vec<u8>> {
+    check_length(response.content_length(), max)?;
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > max {
+            return Err(ValidationError::LimitExceeded("max_jwks_size"));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok(body)
+}
+
+#[cfg(feature = "fetch")]
+impl JWKS {
+    /// Fetch and deserialise a key set from the specified URL using a
+    /// blocking HTTP client.
+    ///
+    /// This function must not be called from within an asynchronous
+    /// runtime. Responses with a status other than `200 OK`, or a
+    /// content type other than JSON, are rejected.
+    pub fn fetch(url: &str) -> JWTResult<JWKS> {
+        JWKS::fetch_with(&reqwest::blocking::Client::new(), url)
+    }
+
+    /// Fetch and deserialise a key set from the specified URL using
+    /// the supplied blocking HTTP client, for example one configured
+    /// with custom timeouts or proxies.
+    pub fn fetch_with(client: &reqwest::blocking::Client, url: &str) -> JWTResult<JWKS> {
+        let response = client.get(url)
+            .header(ACCEPT, ACCEPT_JWKS)
+            .send()?;
+
+        check_response(response.status(), response.headers())?;
+
+        let limits = Limits::default();
+        limits.parse_jwks(&read_body(response, limits.max_jwks_size)?)
+    }
+}
+
+#[cfg(feature = "async")]
+impl JWKS {
+    /// Fetch and deserialise a key set from the specified URL using an
+    /// asynchronous HTTP client.
+    ///
+    /// Responses with a status other than `200 OK`, or a content type
+    /// other than JSON, are rejected.
+    pub async fn fetch_async(url: &str) -> JWTResult<JWKS> {
+        JWKS::fetch_async_with(&reqwest::Client::new(), url).await
+    }
+
+    /// Fetch and deserialise a key set from the specified URL using
+    /// the supplied asynchronous HTTP client.
+    pub async fn fetch_async_with(client: &reqwest::Client, url: &str) -> JWTResult<JWKS> {
+        let response = client.get(url)
+            .header(ACCEPT, ACCEPT_JWKS)
+            .send()
+            .await?;
+
+        check_response(response.status(), response.headers())?;
+
+        let limits = Limits::default();
+        limits.parse_jwks(&read_body_async(response, limits.max_jwks_size).await?)
+    }
+}
@@ -0,0 +1,164 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Implements a key store that loads key sets from a local file.
+//!
+//! This is intended for deployments that cannot reach the JWKS URL of
+//! their identity provider at runtime, and instead receive key sets
+//! through mounted files (e.g. Kubernetes secrets or config maps).
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+use crate::{JWK, JWKS, JWTResult, KeyPolicy, Limits, ValidationError};
+
+/// A key set loaded from a file, together with the file metadata used
+/// to detect changes.
+struct LoadedKeys {
+    jwks: Arc<JWKS>,
+    modified: SystemTime,
+    len: u64,
+}
+
+/// Key store that loads a key set from a file and reloads it whenever
+/// the file changes.
+///
+/// Changes are detected on access by comparing the modification time
+/// and size of the file with those of the loaded version, so no
+/// background task or file system watcher is required. Symbolic links
+/// are followed, which means that atomically replaced mounts are
+/// picked up as well.
+///
+/// If reloading a changed file fails, e.g. because it is still being
+/// written, the previously loaded key set continues to be used and
+/// reloading is attempted again on the next access.
+pub struct FileKeyStore {
+    path: PathBuf,
+    policy: Option<KeyPolicy>,
+    limits: Limits,
+    cache: RwLock<Option<LoadedKeys>>,
+}
+
+impl FileKeyStore {
+    /// Create a key store for the key set in the specified file. The
+    /// file is not read until keys are first requested.
+    pub fn new<P: AsRef<Path>>(path: P) -> FileKeyStore {
+        FileKeyStore {
+            path: path.as_ref().to_path_buf(),
+            policy: None,
+            limits: Limits::default(),
+            cache: RwLock::new(None),
+        }
+    }
+
+    /// Set a key policy that is applied to every loaded key set. Keys
+    /// that do not satisfy the policy are removed from the set.
+    pub fn key_policy(mut self, policy: KeyPolicy) -> FileKeyStore {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// Set the limits on the size of the key set file. Defaults to
+    /// `Limits::default()`.
+    pub fn limits(mut self, limits: Limits) -> FileKeyStore {
+        self.limits = limits;
+        self
+    }
+
+    /// Return the path of the key set file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Return the current key set, loading it from the file if it has
+    /// changed or has not been loaded yet.
+    pub fn keys(&self) -> JWTResult<Arc<JWKS>> {
+        let metadata = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            Err(err) => return self.loaded().ok_or(ValidationError::IO(err)),
+        };
+
+        let modified = metadata.modified()?;
+        let len = metadata.len();
+
+        {
+            let cache = self.cache.read().unwrap();
+            if let Some(ref loaded) = *cache {
+                if loaded.modified == modified && loaded.len == len {
+                    return Ok(loaded.jwks.clone());
+                }
+            }
+        }
+
+        match self.load(modified, len) {
+            Ok(jwks) => Ok(jwks),
+            Err(err) => self.loaded().ok_or(err),
+        }
+    }
+
+    /// Find the signing key with the specified key ID in the current
+    /// key set.
+    ///
+    /// Returns `ValidationError::KeyNotFound` if the key is not part of
+    /// the key set.
+    pub fn find(&self, kid: &str) -> JWTResult<JWK> {
+        match self.keys()?.find_signing_key(kid) {
+            Some(jwk) => Ok(jwk.clone()),
+            None => Err(ValidationError::KeyNotFound(Some(kid.into()))),
+        }
+    }
+
+    /// Load the key set from the file, regardless of whether it has
+    /// changed. Unlike `FileKeyStore::keys`, errors are returned even
+    /// if a previously loaded key set is available.
+    pub fn reload(&self) -> JWTResult<Arc<JWKS>> {
+        let metadata = fs::metadata(&self.path)?;
+        self.load(metadata.modified()?, metadata.len())
+    }
+
+    /// Return the previously loaded key set, if any.
+    fn loaded(&self) -> Option<Arc<JWKS>> {
+        let cache = self.cache.read().unwrap();
+        cache.as_ref().map(|loaded| loaded.jwks.clone())
+    }
+
+    /// Read and deserialise the key set file and store its contents
+    /// together with the supplied metadata.
+    fn load(&self, modified: SystemTime, len: u64) -> JWTResult<Arc<JWKS>> {
+        // Oversized files are rejected before reading them.
+        if len > self.limits.max_jwks_size as u64 {
+            return Err(ValidationError::LimitExceeded("max_jwks_size"));
+        }
+
+        let contents = fs::read(&self.path)?;
+        let mut jwks = self.limits.parse_jwks(&contents)?;
+
+        if let Some(ref policy) = self.policy {
+            jwks.apply_policy(policy);
+        }
+
+        let jwks = Arc::new(jwks);
+        let mut cache = self.cache.write().unwrap();
+        *cache = Some(LoadedKeys {
+            jwks: jwks.clone(),
+            modified,
+            len,
+        });
+
+        Ok(jwks)
+    }
+}
@@ -0,0 +1,244 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+//! Implements a client for OAuth 2.0 token introspection as
+//! specified in [RFC 7662](https://tools.ietf.org/html/rfc7662), which
+//! serves as a fallback for tokens that can not be validated locally.
+//!
+//! This lets services accepting both JWTs and opaque tokens handle
+//! them in a single code path: tokens are validated locally whenever
+//! possible, and only sent to the introspection endpoint of their
+//! issuer if local validation fails for one of the configured reasons.
+
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::sync::Arc;
+
+#[cfg(feature = "fetch")]
+use std::sync::OnceLock;
+
+#[cfg(feature = "async")]
+use crate::fetch::read_body_async;
+#[cfg(feature = "fetch")]
+use crate::fetch::read_body;
+
+use crate::fetch::check_response;
+use crate::{validate_claim_set, ClaimFailure, JWTResult, Limits, ValidJWT, Validation, ValidationError, Validator};
+
+/// Client for the introspection endpoint of a token issuer.
+///
+/// By default, local validation falls back to introspection if the
+/// token is not a JWT (e.g. an opaque token) or is signed with a key
+/// that is not known locally. Other failures, such as expired tokens
+/// or invalid signatures, are returned as they are.
+///
+/// ```rust,no_run
+/// # use alcoholic_jwt::{IntrospectionClient, Validation, Validator};
+/// # fn example(validator: &Validator, token: &str) {
+/// let introspection = IntrospectionClient::new(
+///     "https://auth.test.aprila.no/oauth/introspect", "my-api", Some("secret"),
+/// );
+///
+/// // ... in a request handler:
+/// # #[cfg(feature = "fetch")]
+/// let result = introspection.validate(validator, token);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct IntrospectionClient {
+    endpoint: String,
+    client_id: String,
+    client_secret: Option<String>,
+    fallback: Arc<dyn Fn(&ValidationError) -> bool + Send + Sync>,
+
+    // The blocking client is constructed on first use, as it can not
+    // be created (or dropped) inside of an asynchronous runtime.
+    #[cfg(feature = "fetch")]
+    client: OnceLock<reqwest::blocking::Client>,
+
+    #[cfg(feature = "async")]
+    async_client: reqwest::Client,
+}
+
+impl fmt::Debug for IntrospectionClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // The client secret is intentionally omitted.
+        f.debug_struct("IntrospectionClient")
+            .field("endpoint", &self.endpoint)
+            .field("client_id", &self.client_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Default reasons for falling back to introspection, i.e. tokens
+/// that are not JWTs or signed with unknown keys.
+fn default_fallback(err: &ValidationError) -> bool {
+    matches!(err,
+             ValidationError::InvalidComponents |
+             ValidationError::InvalidBase64(_) |
+             ValidationError::JSON(_) |
+             ValidationError::KeyNotFound(_))
+}
+
+impl IntrospectionClient {
+    /// Create a client for the specified introspection endpoint, which
+    /// authenticates using HTTP Basic authentication with the specified
+    /// client credentials.
+    pub fn new<E, C>(endpoint: E, client_id: C, client_secret: Option<&str>) -> IntrospectionClient
+    where E: Into<String>, C: Into<String> {
+        IntrospectionClient {
+            endpoint: endpoint.into(),
+            client_id: client_id.into(),
+            client_secret: client_secret.map(String::from),
+            fallback: Arc::new(default_fallback),
+
+            #[cfg(feature = "fetch")]
+            client: OnceLock::new(),
+
+            #[cfg(feature = "async")]
+            async_client: reqwest::Client::new(),
+        }
+    }
+
+    /// Set the local validation failures for which the token is
+    /// introspected instead, replacing the default reasons.
+    pub fn fallback_if<F>(mut self, fallback: F) -> IntrospectionClient
+    where F: Fn(&ValidationError) -> bool + Send + Sync + 'static {
+        self.fallback = Arc::new(fallback);
+        self
+    }
+
+    /// Set the blocking HTTP client used for introspection requests,
+    /// for example one configured with custom timeouts.
+    #[cfg(feature = "fetch")]
+    pub fn http_client(mut self, client: reqwest::blocking::Client) -> IntrospectionClient {
+        self.client = OnceLock::from(client);
+        self
+    }
+
+    /// Set the asynchronous HTTP client used for introspection
+    /// requests.
+    #[cfg(feature = "async")]
+    pub fn async_http_client(mut self, client: reqwest::Client) -> IntrospectionClient {
+        self.async_client = client;
+        self
+    }
+
+    /// Validate a token using the specified validator, falling back to
+    /// introspection (see `introspect`) if local validation fails for
+    /// one of the configured reasons.
+    ///
+    /// This function must not be called from within an asynchronous
+    /// runtime, see `validate_async` instead.
+    #[cfg(feature = "fetch")]
+    pub fn validate(&self, validator: &Validator, token: &str) -> JWTResult<ValidJWT> {
+        match validator.validate(token) {
+            Err(ref err) if (self.fallback)(err) => self.introspect(token, validator.prepared_validations()),
+            result => result,
+        }
+    }
+
+    /// Introspect a token and apply the specified validations to the
+    /// introspection response.
+    ///
+    /// Tokens reported as inactive are rejected with a failure of the
+    /// "active"-claim. Responses are subject to the default size limit
+    /// of key set documents (see `Limits`). Otherwise the response
+    /// members are returned as the claims of the token, which has no
+    /// headers. Validations of headers (e.g. `Validation::TokenType`)
+    /// therefore fail for introspected tokens.
+    #[cfg(feature = "fetch")]
+    pub fn introspect(&self, token: &str, validations: &[Validation]) -> JWTResult<ValidJWT> {
+        let (client_id, client_secret) = self.credentials();
+        let client = self.client.get_or_init(reqwest::blocking::Client::new);
+        let response = client.post(&self.endpoint)
+            .header(ACCEPT, "application/json")
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .basic_auth(client_id, client_secret)
+            .body(form_body(token))
+            .send()?;
+
+        check_response(response.status(), response.headers())?;
+        let body = read_body(response, Limits::default().max_jwks_size)?;
+        introspection_result(&body, validations)
+    }
+
+    /// Asynchronous equivalent of `IntrospectionClient::validate`.
+    #[cfg(feature = "async")]
+    pub async fn validate_async(&self, validator: &Validator, token: &str) -> JWTResult<ValidJWT> {
+        match validator.validate_async(token).await {
+            Err(ref err) if (self.fallback)(err) => self.introspect_async(token, validator.prepared_validations()).await,
+            result => result,
+        }
+    }
+
+    /// Asynchronous equivalent of `IntrospectionClient::introspect`.
+    #[cfg(feature = "async")]
+    pub async fn introspect_async(&self, token: &str, validations: &[Validation]) -> JWTResult<ValidJWT> {
+        let (client_id, client_secret) = self.credentials();
+        let response = self.async_client.post(&self.endpoint)
+            .header(ACCEPT, "application/json")
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .basic_auth(client_id, client_secret)
+            .body(form_body(token))
+            .send()
+            .await?;
+
+        check_response(response.status(), response.headers())?;
+        let body = read_body_async(response, Limits::default().max_jwks_size).await?;
+        introspection_result(&body, validations)
+    }
+
+    /// Return the client credentials for HTTP Basic authentication,
+    /// which are form-encoded before being combined (RFC 6749, section
+    /// 2.3.1).
+    fn credentials(&self) -> (String, Option<String>) {
+        (form_encode(&self.client_id), self.client_secret.as_deref().map(form_encode))
+    }
+}
+
+/// Construct the form-encoded body of an introspection request.
+fn form_body(token: &str) -> String {
+    format!("token_type_hint=access_token&token={}", form_encode(token))
+}
+
+/// Encode a value for use in a form-encoded request.
+fn form_encode(value: &str) -> String {
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Map an introspection response to a validated token.
+fn introspection_result(body: &[u8], validations: &[Validation]) -> JWTResult<ValidJWT> {
+    let claims: Map<String, Value> = serde_json::from_slice(body)?;
+    if claims.get("active") != Some(&Value::Bool(true)) {
+        let failure = ClaimFailure::invalid("active", "token is not active");
+        return Err(ValidationError::InvalidClaims(vec![failure]));
+    }
+
+    let headers = Value::Object(Map::new());
+    let claims = Value::Object(claims);
+    validate_claim_set(&headers, &claims, validations)?;
+
+    Ok(ValidJWT { headers, claims })
+}
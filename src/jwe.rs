@@ -0,0 +1,155 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Implements decryption of JSON Web Encryption (JWE) tokens in
+//! compact serialisation as specified in [RFC
+//! 7516](https://tools.ietf.org/html/rfc7516).
+//!
+//! The `RSA-OAEP` and `RSA-OAEP-256` key management algorithms are
+//! supported, together with AES-GCM content encryption (`A128GCM`,
+//! `A192GCM` and `A256GCM`). Compressed payloads are not supported.
+//!
+//! Decrypting a token does not authenticate its sender, so signed
+//! tokens contained in a JWE must still be validated, e.g. using
+//! `validate` or, for nested tokens, `validate_nested`.
+
+use openssl::encrypt::Decrypter;
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Private};
+use openssl::rsa::Padding;
+use openssl::symm::{decrypt_aead, Cipher};
+use serde_json::Value;
+
+use crate::{
+    deserialize_part, jwt_forgiving, media_type_eq, validate_with, JWTResult,
+    KeyProvider, Limits, ValidJWT, Validation, ValidationError,
+};
+
+/// Representation of a decrypted JWE.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecryptedJWE {
+    /// Protected header of the JWE, which specifies the encryption
+    /// algorithms and, for nested tokens, the content type.
+    pub headers: Value,
+
+    /// Decrypted content of the JWE.
+    pub plaintext: Vec<u8>,
+}
+
+impl DecryptedJWE {
+    /// Return the decrypted content as a string, e.g. a signed token
+    /// that can be passed to `validate`.
+    pub fn as_str(&self) -> JWTResult<&str> {
+        std::str::from_utf8(&self.plaintext).map_err(|_| ValidationError::InvalidComponents)
+    }
+}
+
+/// Decrypt a JWE in compact serialisation using the specified RSA
+/// private key.
+///
+/// Returns `ValidationError::DisallowedAlgorithm` if the JWE uses an
+/// unsupported algorithm, and `ValidationError::DecryptionFailed` if
+/// it can not be decrypted with the key. To avoid revealing details
+/// about the failure, all decryption errors are reported the same way.
+pub fn decrypt_jwe(jwe: &str, key: &PKey<Private>) -> JWTResult<DecryptedJWE> {
+    if jwe.len() > Limits::default().max_token_length {
+        return Err(ValidationError::LimitExceeded("max_token_length"));
+    }
+
+    let parts: Vec<&str> = jwe.split('.').collect();
+    let (header_b64, encrypted_key, iv, ciphertext, tag) = match parts[..] {
+        [header, key, iv, ciphertext, tag] => (header, key, iv, ciphertext, tag),
+        _ => return Err(ValidationError::InvalidComponents),
+    };
+
+    let headers: Value = deserialize_part(header_b64)?;
+    let header = |name: &str| headers.get(name).and_then(Value::as_str);
+
+    let oaep_md = match header("alg") {
+        Some("RSA-OAEP") => MessageDigest::sha1(),
+        Some("RSA-OAEP-256") => MessageDigest::sha256(),
+        alg => return Err(ValidationError::DisallowedAlgorithm(alg.map(Into::into))),
+    };
+
+    let cipher = match header("enc") {
+        Some("A128GCM") => Cipher::aes_128_gcm(),
+        Some("A192GCM") => Cipher::aes_192_gcm(),
+        Some("A256GCM") => Cipher::aes_256_gcm(),
+        enc => return Err(ValidationError::DisallowedAlgorithm(enc.map(Into::into))),
+    };
+
+    if headers.get("zip").is_some() {
+        return Err(ValidationError::DisallowedAlgorithm(header("zip").map(Into::into)));
+    }
+
+    let decode = |part: &str| base64::decode_config(part, jwt_forgiving());
+    let cek = decrypt_key(&decode(encrypted_key)?, key, oaep_md)
+        .filter(|cek| cek.len() == cipher.key_len())
+        .ok_or(ValidationError::DecryptionFailed)?;
+
+    // OpenSSL accepts truncated tags, but AES-GCM requires a 128-bit
+    // tag (RFC 7518, section 5.3).
+    let tag = decode(tag)?;
+    if tag.len() != 16 {
+        return Err(ValidationError::DecryptionFailed);
+    }
+
+    // The additional authenticated data is the encoded protected
+    // header, as specified in RFC 7516, section 5.2.
+    let plaintext = decrypt_aead(cipher, &cek, Some(&decode(iv)?), header_b64.as_bytes(),
+                                 &decode(ciphertext)?, &tag)
+        .map_err(|_| ValidationError::DecryptionFailed)?;
+
+    Ok(DecryptedJWE { headers, plaintext })
+}
+
+/// Decrypt a nested JWT, i.e. a signed token contained in a JWE, and
+/// validate the signed token using a key from the specified key
+/// provider, see `validate_with`.
+///
+/// The JWE must specify a content type ("cty"-header) of `JWT`, as
+/// required by RFC 7519 for nested tokens. The returned token contains
+/// the header and claims of the signed token.
+pub fn validate_nested<P>(jwe: &str,
+                          decryption_key: &PKey<Private>,
+                          provider: &P,
+                          validations: Vec<Validation>) -> JWTResult<ValidJWT>
+where P: KeyProvider + ?Sized {
+    let decrypted = decrypt_jwe(jwe, decryption_key)?;
+
+    let nested = decrypted.headers.get("cty")
+        .and_then(Value::as_str)
+        .is_some_and(|cty| media_type_eq(cty, "JWT"));
+
+    if !nested {
+        return Err(ValidationError::InvalidComponents);
+    }
+
+    validate_with(decrypted.as_str()?, provider, validations)
+}
+
+/// Decrypt the content encryption key of a JWE using RSA-OAEP with the
+/// specified digest.
+fn decrypt_key(encrypted_key: &[u8], key: &PKey<Private>, md: MessageDigest) -> Option<Vec<u8>> {
+    let mut decrypter = Decrypter::new(key).ok()?;
+    decrypter.set_rsa_padding(Padding::PKCS1_OAEP).ok()?;
+    decrypter.set_rsa_oaep_md(md).ok()?;
+    decrypter.set_rsa_mgf1_md(md).ok()?;
+
+    let mut cek = vec![0; decrypter.decrypt_len(encrypted_key).ok()?];
+    let len = decrypter.decrypt(encrypted_key, &mut cek).ok()?;
+    cek.truncate(len);
+    Some(cek)
+}
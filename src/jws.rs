@@ -0,0 +1,215 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Implements parsing of the JSON serialisations of JWS as specified
+//! in [RFC 7515, section 7.2](https://tools.ietf.org/html/rfc7515#section-7.2).
+//!
+//! Both the general serialisation, which can carry several
+//! signatures, and the flattened serialisation of a single signature
+//! are supported. Each signature is validated by converting it into
+//! the compact serialisation used by all other functions of this
+//! library.
+
+use serde_json::{Map, Value};
+
+use crate::{
+    deserialize_part, token_limits, validate_shared_claim_set, verify_against, JWTResult,
+    KeyProvider, Limits, ValidJWT, Validation, ValidationError,
+};
+
+/// A single signature of a JWS in JSON serialisation.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JWSSignature {
+    /// The base64url-encoded protected header.
+    pub protected: String,
+
+    /// The unprotected header, whose members are not covered by the
+    /// signature.
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub header: Map<String, Value>,
+
+    /// The base64url-encoded signature.
+    pub signature: String,
+}
+
+/// A JWS in JSON serialisation, in either its general or its
+/// flattened form.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JWSJson {
+    /// The base64url-encoded payload.
+    pub payload: String,
+
+    /// The signatures of the JWS. The flattened serialisation always
+    /// contains exactly one signature.
+    pub signatures: Vec<JWSSignature>,
+}
+
+/// Raw representation of both serialisations, which only differ in
+/// whether the signature members are nested.
+#[derive(Deserialize)]
+struct RawJWS {
+    payload: String,
+    signatures: Option<Vec<JWSSignature>>,
+
+    #[serde(flatten)]
+    flattened: Option<JWSSignature>,
+}
+
+impl JWSSignature {
+    /// Determine the key ID of this signature, which may be specified
+    /// in either its unprotected or its protected header.
+    pub fn kid(&self) -> JWTResult<Option<String>> {
+        if let Some(kid) = self.header.get("kid") {
+            return Ok(kid.as_str().map(Into::into));
+        }
+
+        let protected: Value = deserialize_part(&self.protected)?;
+        Ok(protected.get("kid").and_then(Value::as_str).map(Into::into))
+    }
+}
+
+impl JWSJson {
+    /// Parse a JWS in general or flattened JSON serialisation.
+    ///
+    /// Documents exceeding the default token length limit are
+    /// rejected before being parsed. Signatures without a protected
+    /// header are not supported.
+    pub fn parse(json: &str) -> JWTResult<JWSJson> {
+        JWSJson::parse_limited(json, &Limits::default())
+    }
+
+    /// Parse a JWS in JSON serialisation, applying the specified
+    /// limits on its size.
+    fn parse_limited(json: &str, limits: &Limits) -> JWTResult<JWSJson> {
+        if json.len() > limits.max_token_length {
+            return Err(ValidationError::LimitExceeded("max_token_length"));
+        }
+
+        let raw: RawJWS = serde_json::from_str(json)?;
+        let signatures = match (raw.signatures, raw.flattened) {
+            (Some(signatures), None) if !signatures.is_empty() => signatures,
+            (None, Some(signature)) => vec![signature],
+            _ => return Err(ValidationError::InvalidComponents),
+        };
+
+        Ok(JWSJson { payload: raw.payload, signatures })
+    }
+
+    /// Return the compact serialisation of one of the signatures.
+    ///
+    /// The unprotected header is not part of the compact
+    /// serialisation and thus not included.
+    pub fn compact(&self, signature: &JWSSignature) -> String {
+        format!("{}.{}.{}", signature.protected, self.payload, signature.signature)
+    }
+
+    /// Verify a single signature using a key from the specified key
+    /// provider, see `validate_jws_json`. The thumbprint of the key is
+    /// returned together with the decoded token.
+    fn verify_signature<P>(&self,
+                           signature: &JWSSignature,
+                           provider: &P,
+                           limits: &Limits) -> JWTResult<(ValidJWT, String)>
+    where P: KeyProvider + ?Sized {
+        let kid = signature.kid()?;
+        let jwk = provider.key_for(kid.as_deref())?;
+        let jwt = verify_against(&self.compact(signature), Some(jwk.as_ref()), limits)?;
+        Ok((jwt, jwk.thumbprint()))
+    }
+}
+
+/// Policy for validating JWS with several signatures.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignaturePolicy {
+    /// At least one of the signatures must be valid.
+    Any,
+
+    /// All signatures must be valid, and each of them must have been
+    /// made with a different key. This is useful for documents that
+    /// are co-signed by several parties.
+    All,
+}
+
+/// Validate a JWS in general or flattened JSON serialisation using
+/// keys from the specified key provider, and optionally apply claim
+/// validations.
+///
+/// Keys are selected based on the `kid` member of the unprotected or
+/// protected header of each signature. The JWS is valid if any of its
+/// signatures is valid, in which case the returned token contains the
+/// protected header of that signature. Unprotected headers are only
+/// used for selecting keys.
+///
+/// If no signature is valid, the error encountered for the last
+/// signature is returned.
+pub fn validate_jws_json<P>(jws: &str,
+                            provider: &P,
+                            validations: Vec<Validation>) -> JWTResult<ValidJWT>
+where P: KeyProvider + ?Sized {
+    let mut valid = validate_jws_json_with_policy(jws, provider, SignaturePolicy::Any, validations)?;
+    Ok(valid.remove(0))
+}
+
+/// Validate a JWS in JSON serialisation as in `validate_jws_json`,
+/// but according to the specified policy for multiple signatures.
+///
+/// Returns a token for each valid signature, in the order of the
+/// signatures. With `SignaturePolicy::All` this includes every
+/// signature of the JWS, so callers requiring a minimum number of
+/// signatures can check the length of the result.
+///
+/// If the policy is not satisfied, the error encountered for the
+/// first invalid signature (`All`) or the last signature (`Any`) is
+/// returned.
+///
+/// Claim validations are applied once the signatures have been
+/// verified, and must pass together with the protected header of
+/// every valid signature.
+pub fn validate_jws_json_with_policy<P>(jws: &str,
+                                        provider: &P,
+                                        policy: SignaturePolicy,
+                                        validations: Vec<Validation>) -> JWTResult<Vec<ValidJWT>>
+where P: KeyProvider + ?Sized {
+    let limits = token_limits(&validations);
+    let jws = JWSJson::parse_limited(jws, &limits)?;
+    let mut valid = vec![];
+    let mut thumbprints = vec![];
+    let mut error = ValidationError::InvalidSignature;
+
+    for signature in &jws.signatures {
+        match jws.verify_signature(signature, provider, &limits) {
+            Ok((jwt, thumbprint)) => {
+                // Signatures made with the same key only count once.
+                if policy == SignaturePolicy::All && thumbprints.contains(&thumbprint) {
+                    return Err(ValidationError::InvalidSignature);
+                }
+
+                thumbprints.push(thumbprint);
+                valid.push(jwt);
+            },
+            Err(err) if policy == SignaturePolicy::All => return Err(err),
+            Err(err) => error = err,
+        }
+    }
+
+    if valid.is_empty() {
+        return Err(error);
+    }
+
+    // All signatures share the payload, so its claims are validated
+    // (and its use recorded) only once.
+    validate_shared_claim_set(valid.iter().map(|jwt| &jwt.headers), &valid[0].claims, &validations)?;
+    Ok(valid)
+}
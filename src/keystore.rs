@@ -0,0 +1,932 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Implements a cache for key sets that are retrieved over HTTP.
+//!
+//! Key sets are cached for as long as the `Cache-Control` or
+//! `Expires` headers of the JWKS response allow, and are fetched
+//! again once they become stale.
+
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, AGE, CACHE_CONTROL, DATE, ETAG, EXPIRES};
+use reqwest::header::{IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+#[cfg(feature = "fetch")]
+use std::ops::DerefMut;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+#[cfg(feature = "fetch")]
+use std::sync::OnceLock;
+
+use crate::cache::{CacheBackend, CacheEntry};
+use crate::fetch::{check_response, ACCEPT_JWKS};
+#[cfg(feature = "fetch")]
+use crate::fetch::read_body;
+#[cfg(feature = "async")]
+use crate::fetch::read_body_async;
+use crate::{JWK, JWKS, JWTResult, KeyPolicy, Limits, MetricsCache, MetricsSink, Validation, ValidationError};
+
+/// Default lifetime of cached keys if the JWKS response does not
+/// specify one.
+const DEFAULT_FALLBACK_TTL: Duration = Duration::from_secs(600);
+
+/// Default minimum time between fetches that are not explicitly
+/// requested.
+const DEFAULT_REFRESH_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// Longest lifetime of cached keys, regardless of the lifetime
+/// specified by the JWKS response or the fallback TTL.
+const MAX_CACHE_LIFETIME: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// Shortest interval at which background refreshes are performed.
+#[cfg(feature = "tokio")]
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A key set fetched from a JWKS URL, together with the time at which
+/// it becomes stale.
+struct CachedKeys {
+    jwks: Arc<JWKS>,
+    expires_at: SystemTime,
+
+    // Validators used for conditional requests
+    etag: Option<HeaderValue>,
+    last_modified: Option<HeaderValue>,
+
+    // Number of the fetch attempt that produced this key set
+    attempt: u64,
+}
+
+/// State shared by all callers that attempt to fetch the key set.
+/// Holding the lock around this state ensures that only one fetch is
+/// in flight at a time.
+struct RefreshState {
+    last_attempt: Option<Instant>,
+}
+
+// The lock must be held across fetches, which requires an
+// asynchronous mutex if asynchronous fetching is enabled.
+#[cfg(feature = "async")]
+type RefreshLock = tokio::sync::Mutex<RefreshState>;
+
+#[cfg(not(feature = "async"))]
+type RefreshLock = std::sync::Mutex<RefreshState>;
+
+/// Cache for the key set published at a JWKS URL.
+///
+/// The key set is fetched when it is first requested and refreshed
+/// once the lifetime specified by the `Cache-Control: max-age` or
+/// `Expires` headers of the response has passed. If the response does
+/// not specify a lifetime, a fallback lifetime is used instead.
+///
+/// If the response contains an `ETag` or `Last-Modified` header,
+/// subsequent fetches are conditional requests. A `304 Not Modified`
+/// response extends the lifetime of the cached key set without
+/// transferring and parsing it again.
+///
+/// When a key ID that is not part of the cached key set is requested,
+/// the key set is fetched again to pick up rotated keys.
+///
+/// Fetches that are not explicitly requested (see `KeyStore::refresh`)
+/// happen at most once per refresh cool-down period, which protects
+/// the identity provider if its responses can not be cached or if it
+/// is failing. In between, the key set from the last fetch is used if
+/// that fetch succeeded, otherwise stale keys (within the maximum
+/// staleness) or an error are returned.
+///
+/// Optionally, stale keys can continue to be used for a limited time
+/// if the key set can not be fetched, see `KeyStore::max_staleness`.
+///
+/// Concurrent requests that require the key set to be fetched are
+/// coalesced into a single fetch, whose result is shared by all of
+/// them.
+///
+/// A `KeyStore` can be shared between threads, for example by
+/// wrapping it in an `Arc`.
+pub struct KeyStore {
+    url: String,
+    issuer: Option<String>,
+    fallback_ttl: Duration,
+    refresh_cooldown: Duration,
+    max_staleness: Duration,
+    policy: Option<KeyPolicy>,
+    limits: Limits,
+    cache: RwLock<Option<CachedKeys>>,
+    backend: Option<Arc<dyn CacheBackend>>,
+    metrics: Option<Arc<dyn MetricsSink>>,
+    refresh_lock: RefreshLock,
+
+    // Number of fetch attempts that have been started. This can be
+    // read without holding the refresh lock.
+    attempts: AtomicU64,
+
+    // The blocking client is constructed on first use, as it can not
+    // be created (or dropped) inside of an asynchronous runtime.
+    #[cfg(feature = "fetch")]
+    blocking_client: OnceLock<reqwest::blocking::Client>,
+
+    #[cfg(feature = "async")]
+    client: reqwest::Client,
+}
+
+impl KeyStore {
+    /// Create a key store for the key set published at the specified
+    /// URL. No keys are fetched until they are first requested.
+    pub fn new(url: &str) -> KeyStore {
+        KeyStore {
+            url: url.into(),
+            issuer: None,
+            fallback_ttl: DEFAULT_FALLBACK_TTL,
+            refresh_cooldown: DEFAULT_REFRESH_COOLDOWN,
+            max_staleness: Duration::from_secs(0),
+            policy: None,
+            limits: Limits::default(),
+            cache: RwLock::new(None),
+            backend: None,
+            metrics: None,
+            refresh_lock: RefreshLock::new(RefreshState { last_attempt: None }),
+            attempts: AtomicU64::new(0),
+
+            #[cfg(feature = "fetch")]
+            blocking_client: OnceLock::new(),
+
+            #[cfg(feature = "async")]
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Set the lifetime of cached keys for JWKS responses that do not
+    /// specify one. Defaults to ten minutes. Lifetimes are limited to
+    /// one year.
+    pub fn fallback_ttl(mut self, ttl: Duration) -> KeyStore {
+        self.fallback_ttl = ttl;
+        self
+    }
+
+    /// Set the minimum time between fetches of the key set that are
+    /// not explicitly requested, e.g. fetches caused by stale keys or
+    /// requests for unknown key IDs. Defaults to one minute.
+    pub fn refresh_cooldown(mut self, cooldown: Duration) -> KeyStore {
+        self.refresh_cooldown = cooldown;
+        self
+    }
+
+    /// Set an external cache backend that is shared with other key
+    /// stores, see `CacheBackend`.
+    pub fn cache_backend(mut self, backend: Arc<dyn CacheBackend>) -> KeyStore {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// Set how long a key set may continue to be used after it has
+    /// become stale, while it is being fetched again or if fetching it
+    /// fails (e.g. because the identity provider is unreachable).
+    /// Defaults to zero, i.e. stale keys are never used, while
+    /// `Duration::MAX` lets them be used indefinitely.
+    pub fn max_staleness(mut self, max_staleness: Duration) -> KeyStore {
+        self.max_staleness = max_staleness;
+        self
+    }
+
+    /// Set a sink for metrics about fetches of the key set and
+    /// lookups in the cached key set.
+    pub fn metrics(mut self, metrics: Arc<dyn MetricsSink>) -> KeyStore {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Set a key policy that is applied to every fetched key set.
+    /// Keys that do not satisfy the policy are removed from the set.
+    pub fn key_policy(mut self, policy: KeyPolicy) -> KeyStore {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// Set the limits on the size of fetched key sets. Defaults to
+    /// `Limits::default()`.
+    pub fn limits(mut self, limits: Limits) -> KeyStore {
+        self.limits = limits;
+        self
+    }
+
+    /// URL from which keys are fetched.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Canonical issuer identifier, if this key store was created
+    /// using OpenID Connect discovery.
+    pub fn issuer(&self) -> Option<&str> {
+        self.issuer.as_deref()
+    }
+
+    /// Validation of the issuer claim against the canonical issuer
+    /// identifier, if this key store was created using OpenID Connect
+    /// discovery.
+    pub fn issuer_validation(&self) -> Option<Validation> {
+        self.issuer.clone().map(Validation::Issuer)
+    }
+
+    /// Return the currently cached key set, if it is not stale. No
+    /// keys are fetched by this function.
+    pub fn cached(&self) -> Option<Arc<JWKS>> {
+        let cache = self.cache.read().unwrap();
+        match *cache {
+            Some(ref cached) if cached.expires_at > SystemTime::now() => {
+                Some(cached.jwks.clone())
+            },
+            _ => None,
+        }
+    }
+
+    /// Return the cached key set if it is stale, but has been stale for
+    /// no longer than the maximum staleness.
+    fn stale(&self) -> Option<Arc<JWKS>> {
+        let cache = self.cache.read().unwrap();
+        match *cache {
+            Some(ref cached) if cached.expires_at.checked_add(self.max_staleness)
+                .is_none_or(|limit| limit > SystemTime::now()) => {
+                Some(cached.jwks.clone())
+            },
+            _ => None,
+        }
+    }
+
+    /// Report a lookup in the cached key set to the metrics sink.
+    fn record_lookup(&self, hit: bool) {
+        if let Some(ref metrics) = self.metrics {
+            metrics.cache_lookup(MetricsCache::Keys, hit);
+        }
+    }
+
+    /// Report the outcome of a fetch of the key set to the metrics
+    /// sink.
+    fn record_refresh(&self, result: JWTResult<Arc<JWKS>>) -> JWTResult<Arc<JWKS>> {
+        if let Some(ref metrics) = self.metrics {
+            metrics.key_refresh(result.as_ref().map(|_| ()));
+        }
+
+        result
+    }
+
+    /// Return the stale key set after fetching it failed, or the error
+    /// if it has been stale for too long.
+    fn stale_after(&self, err: ValidationError) -> JWTResult<Arc<JWKS>> {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(url = %self.url, error = err.kind(), "fetching key set failed");
+
+        self.stale().ok_or(err)
+    }
+
+    /// Check whether another caller is currently fetching the key set.
+    fn refresh_in_progress(&self) -> bool {
+        self.refresh_lock.try_lock().is_err()
+    }
+
+    /// Decide whether a caller that has acquired the refresh lock
+    /// needs to fetch the key set itself.
+    ///
+    /// `seen` is the number of fetch attempts that had been started
+    /// before the caller started waiting for the lock. If another
+    /// attempt was made in the meantime, its result is returned
+    /// instead of fetching again. Callers looking for an unknown key
+    /// ID (`kid`) fetch even if the cached key set is fresh, all other
+    /// callers only fetch if it is stale. Both are subject to the
+    /// refresh cool-down.
+    fn coalesce(&self,
+                state: &RefreshState,
+                seen: u64,
+                kid: Option<&str>) -> Option<JWTResult<Arc<JWKS>>> {
+        if kid.is_none() {
+            if let Some(jwks) = self.cached() {
+                return Some(Ok(jwks));
+            }
+        }
+
+        if self.attempts.load(Ordering::SeqCst) != seen {
+            let cache = self.cache.read().unwrap();
+            return Some(match *cache {
+                Some(ref cached) if cached.attempt > seen => Ok(cached.jwks.clone()),
+                _ => Err(ValidationError::RefreshFailed),
+            });
+        }
+
+        if let Some(last_attempt) = state.last_attempt {
+            if last_attempt.elapsed() < self.refresh_cooldown {
+                return Some(match kid {
+                    Some(kid) => Err(ValidationError::KeyNotFound(Some(kid.into()))),
+                    None => self.latest().or_else(|| self.stale()).ok_or(ValidationError::RefreshFailed),
+                });
+            }
+        }
+
+        None
+    }
+
+    /// Return the cached key set if it was produced by the most recent
+    /// fetch attempt, i.e. if that attempt succeeded, even if the key
+    /// set is already stale.
+    fn latest(&self) -> Option<Arc<JWKS>> {
+        let cache = self.cache.read().unwrap();
+        match *cache {
+            Some(ref cached) if cached.attempt == self.attempts.load(Ordering::SeqCst) => {
+                Some(cached.jwks.clone())
+            },
+            _ => None,
+        }
+    }
+
+    /// Record the start of a fetch attempt. Must be called while
+    /// holding the refresh lock.
+    fn begin_attempt(&self, state: &mut RefreshState) {
+        state.last_attempt = Some(Instant::now());
+        self.attempts.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Return the headers for a conditional request based on the
+    /// validators of the cached key set, if any.
+    fn conditional_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let cache = self.cache.read().unwrap();
+
+        if let Some(ref cached) = *cache {
+            if let Some(ref etag) = cached.etag {
+                headers.insert(IF_NONE_MATCH, etag.clone());
+            }
+
+            if let Some(ref last_modified) = cached.last_modified {
+                headers.insert(IF_MODIFIED_SINCE, last_modified.clone());
+            }
+        }
+
+        headers
+    }
+
+    /// Extend the lifetime of the cached key set after the server
+    /// indicated that it has not been modified. Returns `None` if no
+    /// key set is cached.
+    fn revalidate(&self, headers: &HeaderMap) -> Option<Arc<JWKS>> {
+        let now = SystemTime::now();
+        let ttl = cache_lifetime(headers, now).unwrap_or(self.fallback_ttl).min(MAX_CACHE_LIFETIME);
+        let mut cache = self.cache.write().unwrap();
+        let cached = cache.as_mut()?;
+
+        cached.expires_at = expiry(now, ttl);
+        cached.attempt = self.attempts.load(Ordering::SeqCst);
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(url = %self.url, ttl = ttl.as_secs(), "key set not modified");
+
+        // The server may send updated validators along with the 304
+        if let Some(etag) = headers.get(ETAG) {
+            cached.etag = Some(etag.clone());
+        }
+
+        if let Some(last_modified) = headers.get(LAST_MODIFIED) {
+            cached.last_modified = Some(last_modified.clone());
+        }
+
+        Some(cached.jwks.clone())
+    }
+
+    /// Use an entry loaded from the cache backend if it is fresh and,
+    /// if a key ID is specified, contains the requested key.
+    fn load_entry(&self, entry: CacheEntry, kid: Option<&str>) -> Option<Arc<JWKS>> {
+        if entry.expires_at <= SystemTime::now() {
+            return None;
+        }
+
+        let mut jwks = self.limits.parse_jwks(entry.jwks.as_bytes()).ok()?;
+        if let Some(ref policy) = self.policy {
+            jwks.apply_policy(policy);
+        }
+
+        if let Some(kid) = kid {
+            jwks.find_signing_key(kid)?;
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(url = %self.url, "loaded key set from cache backend");
+
+        let jwks = Arc::new(jwks);
+        let header_value = |value: Option<String>| {
+            value.and_then(|value| HeaderValue::from_str(&value).ok())
+        };
+
+        *self.cache.write().unwrap() = Some(CachedKeys {
+            jwks: jwks.clone(),
+            expires_at: entry.expires_at,
+            etag: header_value(entry.etag),
+            last_modified: header_value(entry.last_modified),
+            attempt: self.attempts.load(Ordering::SeqCst),
+        });
+
+        Some(jwks)
+    }
+
+    /// Construct an entry for the cache backend from the cached key
+    /// set.
+    fn shared_entry(&self) -> Option<CacheEntry> {
+        let cache = self.cache.read().unwrap();
+        let cached = cache.as_ref()?;
+        let header_value = |value: &Option<HeaderValue>| {
+            value.as_ref().and_then(|value| value.to_str().ok()).map(Into::into)
+        };
+
+        Some(CacheEntry {
+            jwks: serde_json::to_string(&*cached.jwks).ok()?,
+            expires_at: cached.expires_at,
+            etag: header_value(&cached.etag),
+            last_modified: header_value(&cached.last_modified),
+        })
+    }
+
+    /// Handle a `304 Not Modified` response to a conditional request.
+    fn not_modified(&self, headers: &HeaderMap) -> JWTResult<Arc<JWKS>> {
+        self.revalidate(headers)
+            .ok_or(ValidationError::UnexpectedStatus(StatusCode::NOT_MODIFIED.as_u16()))
+    }
+
+    /// Store a freshly fetched key set in the cache.
+    fn store(&self, mut jwks: JWKS, headers: &HeaderMap) -> Arc<JWKS> {
+        if let Some(ref policy) = self.policy {
+            jwks.apply_policy(policy);
+        }
+
+        let now = SystemTime::now();
+        let ttl = cache_lifetime(headers, now).unwrap_or(self.fallback_ttl).min(MAX_CACHE_LIFETIME);
+        let jwks = Arc::new(jwks);
+
+        #[cfg(feature = "tracing")]
+        tracing::info!(url = %self.url, keys = jwks.keys.len(), ttl = ttl.as_secs(), "fetched key set");
+
+        *self.cache.write().unwrap() = Some(CachedKeys {
+            jwks: jwks.clone(),
+            expires_at: expiry(now, ttl),
+            etag: headers.get(ETAG).cloned(),
+            last_modified: headers.get(LAST_MODIFIED).cloned(),
+            attempt: self.attempts.load(Ordering::SeqCst),
+        });
+
+        jwks
+    }
+}
+
+/// Subset of the OpenID Provider Metadata that is relevant for
+/// validating tokens. See [OpenID Connect Discovery, section
+/// 3](https://openid.net/specs/openid-connect-discovery-1_0.html#ProviderMetadata).
+#[derive(Deserialize)]
+pub(crate) struct ProviderMetadata {
+    issuer: String,
+    jwks_uri: String,
+}
+
+/// Location of the OpenID Provider Configuration document for an
+/// issuer.
+fn discovery_url(issuer: &str) -> String {
+    format!("{}/.well-known/openid-configuration", issuer.trim_end_matches('/'))
+}
+
+impl ProviderMetadata {
+    /// Create a key store from the provider metadata, after checking
+    /// that it was published for the requested issuer.
+    ///
+    /// Trailing slashes are not considered when comparing issuers, but
+    /// the issuer identifier published by the provider is retained as
+    /// it is the one used in tokens.
+    pub(crate) fn into_keystore(self, issuer: &str) -> JWTResult<KeyStore> {
+        if self.issuer.trim_end_matches('/') != issuer.trim_end_matches('/') {
+            return Err(ValidationError::IssuerMismatch(self.issuer));
+        }
+
+        let mut store = KeyStore::new(&self.jwks_uri);
+        store.issuer = Some(self.issuer);
+        Ok(store)
+    }
+}
+
+#[cfg(feature = "fetch")]
+impl KeyStore {
+    /// Create a key store for an OpenID Connect issuer by retrieving
+    /// its `/.well-known/openid-configuration` document using a
+    /// blocking HTTP client.
+    ///
+    /// The canonical issuer identifier from the document is recorded
+    /// and can be used for validation through
+    /// `KeyStore::issuer_validation`. The document is subject to the
+    /// default `Limits::max_jwks_size`.
+    pub fn from_issuer(issuer: &str) -> JWTResult<KeyStore> {
+        let client = reqwest::blocking::Client::new();
+        let response = client.get(discovery_url(issuer))
+            .header(ACCEPT, "application/json")
+            .send()?;
+
+        check_response(response.status(), response.headers())?;
+
+        let body = read_body(response, Limits::default().max_jwks_size)?;
+        let metadata: ProviderMetadata = serde_json::from_slice(&body)?;
+        let store = metadata.into_keystore(issuer)?;
+        let _ = store.blocking_client.set(client);
+        Ok(store)
+    }
+
+    /// Return the cached key set, fetching it using a blocking HTTP
+    /// client if it is stale or has not been fetched yet.
+    ///
+    /// Stale keys are returned within the maximum staleness if another
+    /// caller is already fetching the key set, or if fetching it fails.
+    ///
+    /// This function must not be called from within an asynchronous
+    /// runtime, use `KeyStore::keys_async` instead.
+    pub fn keys(&self) -> JWTResult<Arc<JWKS>> {
+        let cached = self.cached();
+        self.record_lookup(cached.is_some());
+
+        if let Some(jwks) = cached {
+            #[cfg(feature = "tracing")]
+            tracing::trace!(url = %self.url, "key set cache hit");
+            return Ok(jwks);
+        }
+
+        if self.refresh_in_progress() {
+            if let Some(jwks) = self.stale() {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(url = %self.url, "using stale key set during refresh");
+                return Ok(jwks);
+            }
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(url = %self.url, "key set cache miss");
+
+        self.refresh_coalesced(None)
+            .or_else(|err| self.stale_after(err))
+    }
+
+    /// Find the signing key with the specified key ID, fetching the key
+    /// set again if the key is unknown and the refresh cool-down has
+    /// passed.
+    ///
+    /// Returns `ValidationError::KeyNotFound` if the key is not part of
+    /// the key set.
+    pub fn find(&self, kid: &str) -> JWTResult<JWK> {
+        if let Some(jwk) = self.keys()?.find_signing_key(kid) {
+            return Ok(jwk.clone());
+        }
+
+        match self.refresh_coalesced(Some(kid))?.find_signing_key(kid) {
+            Some(jwk) => Ok(jwk.clone()),
+            None => Err(ValidationError::KeyNotFound(Some(kid.into()))),
+        }
+    }
+
+    /// Fetch the key set using a blocking HTTP client, regardless of
+    /// whether the cached key set is stale.
+    pub fn refresh(&self) -> JWTResult<Arc<JWKS>> {
+        let mut state = self.lock_blocking();
+        self.begin_attempt(&mut state);
+        self.fetch_blocking()
+    }
+
+    /// Fetch the key set unless a concurrent caller has already done
+    /// so, see `KeyStore::coalesce`.
+    fn refresh_coalesced(&self, kid: Option<&str>) -> JWTResult<Arc<JWKS>> {
+        let seen = self.attempts.load(Ordering::SeqCst);
+        let mut state = self.lock_blocking();
+
+        if let Some(result) = self.coalesce(&state, seen, kid) {
+            return result;
+        }
+
+        self.begin_attempt(&mut state);
+
+        if let Some(jwks) = self.load_shared(kid) {
+            return Ok(jwks);
+        }
+
+        self.fetch_blocking()
+    }
+
+    /// Load a fresh key set from the cache backend, if any.
+    fn load_shared(&self, kid: Option<&str>) -> Option<Arc<JWKS>> {
+        let entry = self.backend.as_ref()?.load(&self.url).ok()??;
+        self.load_entry(entry, kid)
+    }
+
+    /// Write the cached key set to the cache backend, if any.
+    fn share(&self, jwks: Arc<JWKS>) -> Arc<JWKS> {
+        if let (Some(backend), Some(entry)) = (self.backend.as_ref(), self.shared_entry()) {
+            let _ = backend.store(&self.url, &entry);
+        }
+
+        jwks
+    }
+
+    /// Acquire the refresh lock from a synchronous context.
+    fn lock_blocking(&self) -> impl DerefMut<Target = RefreshState> + '_ {
+        #[cfg(feature = "async")]
+        return self.refresh_lock.blocking_lock();
+
+        #[cfg(not(feature = "async"))]
+        return self.refresh_lock.lock().unwrap();
+    }
+
+    /// Perform the actual fetch of the key set.
+    fn fetch_blocking(&self) -> JWTResult<Arc<JWKS>> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("fetch_jwks", url = %self.url).entered();
+
+        let result = self.request_blocking();
+        self.record_refresh(result)
+    }
+
+    /// Request the key set from its URL, see `fetch_blocking`.
+    fn request_blocking(&self) -> JWTResult<Arc<JWKS>> {
+        let client = self.blocking_client.get_or_init(reqwest::blocking::Client::new);
+        let response = client.get(&self.url)
+            .header(ACCEPT, ACCEPT_JWKS)
+            .headers(self.conditional_headers())
+            .send()?;
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return self.not_modified(response.headers()).map(|jwks| self.share(jwks));
+        }
+
+        check_response(response.status(), response.headers())?;
+
+        let headers = response.headers().clone();
+        let jwks = self.limits.parse_jwks(&read_body(response, self.limits.max_jwks_size)?)?;
+        Ok(self.share(self.store(jwks, &headers)))
+    }
+}
+
+#[cfg(feature = "async")]
+impl KeyStore {
+    /// Create a key store for an OpenID Connect issuer by retrieving
+    /// its `/.well-known/openid-configuration` document using an
+    /// asynchronous HTTP client. See `KeyStore::from_issuer`.
+    pub async fn from_issuer_async(issuer: &str) -> JWTResult<KeyStore> {
+        let client = reqwest::Client::new();
+        let response = client.get(discovery_url(issuer))
+            .header(ACCEPT, "application/json")
+            .send()
+            .await?;
+
+        check_response(response.status(), response.headers())?;
+
+        let body = read_body_async(response, Limits::default().max_jwks_size).await?;
+        let metadata: ProviderMetadata = serde_json::from_slice(&body)?;
+        let mut store = metadata.into_keystore(issuer)?;
+        store.client = client;
+        Ok(store)
+    }
+
+    /// Return the cached key set, fetching it using an asynchronous
+    /// HTTP client if it is stale or has not been fetched yet.
+    pub async fn keys_async(&self) -> JWTResult<Arc<JWKS>> {
+        let cached = self.cached();
+        self.record_lookup(cached.is_some());
+
+        if let Some(jwks) = cached {
+            #[cfg(feature = "tracing")]
+            tracing::trace!(url = %self.url, "key set cache hit");
+            return Ok(jwks);
+        }
+
+        if self.refresh_in_progress() {
+            if let Some(jwks) = self.stale() {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(url = %self.url, "using stale key set during refresh");
+                return Ok(jwks);
+            }
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(url = %self.url, "key set cache miss");
+
+        self.refresh_coalesced_async(None).await
+            .or_else(|err| self.stale_after(err))
+    }
+
+    /// Find the signing key with the specified key ID, fetching the key
+    /// set again if the key is unknown and the refresh cool-down has
+    /// passed. This is the asynchronous equivalent of
+    /// `KeyStore::find`.
+    pub async fn find_async(&self, kid: &str) -> JWTResult<JWK> {
+        if let Some(jwk) = self.keys_async().await?.find_signing_key(kid) {
+            return Ok(jwk.clone());
+        }
+
+        match self.refresh_coalesced_async(Some(kid)).await?.find_signing_key(kid) {
+            Some(jwk) => Ok(jwk.clone()),
+            None => Err(ValidationError::KeyNotFound(Some(kid.into()))),
+        }
+    }
+
+    /// Fetch the key set using an asynchronous HTTP client,
+    /// regardless of whether the cached key set is stale.
+    pub async fn refresh_async(&self) -> JWTResult<Arc<JWKS>> {
+        let mut state = self.refresh_lock.lock().await;
+        self.begin_attempt(&mut state);
+        self.fetch_async().await
+    }
+
+    /// Asynchronous equivalent of `KeyStore::refresh_coalesced`.
+    async fn refresh_coalesced_async(&self, kid: Option<&str>) -> JWTResult<Arc<JWKS>> {
+        let seen = self.attempts.load(Ordering::SeqCst);
+        let mut state = self.refresh_lock.lock().await;
+
+        if let Some(result) = self.coalesce(&state, seen, kid) {
+            return result;
+        }
+
+        self.begin_attempt(&mut state);
+
+        if let Some(jwks) = self.load_shared_async(kid).await {
+            return Ok(jwks);
+        }
+
+        self.fetch_async().await
+    }
+
+    /// Asynchronous equivalent of `KeyStore::load_shared`.
+    async fn load_shared_async(&self, kid: Option<&str>) -> Option<Arc<JWKS>> {
+        let entry = self.backend.as_ref()?.load_async(&self.url).await.ok()??;
+        self.load_entry(entry, kid)
+    }
+
+    /// Asynchronous equivalent of `KeyStore::share`.
+    async fn share_async(&self, jwks: Arc<JWKS>) -> Arc<JWKS> {
+        if let (Some(backend), Some(entry)) = (self.backend.as_ref(), self.shared_entry()) {
+            let _ = backend.store_async(&self.url, &entry).await;
+        }
+
+        jwks
+    }
+
+    /// Perform the actual fetch of the key set.
+    async fn fetch_async(&self) -> JWTResult<Arc<JWKS>> {
+        let request = self.request_async();
+
+        #[cfg(feature = "tracing")]
+        let request = tracing::Instrument::instrument(request, tracing::debug_span!("fetch_jwks", url = %self.url));
+
+        self.record_refresh(request.await)
+    }
+
+    /// Request the key set from its URL, see `fetch_async`.
+    async fn request_async(&self) -> JWTResult<Arc<JWKS>> {
+        let response = self.client.get(&self.url)
+            .header(ACCEPT, ACCEPT_JWKS)
+            .headers(self.conditional_headers())
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            let jwks = self.not_modified(response.headers())?;
+            return Ok(self.share_async(jwks).await);
+        }
+
+        check_response(response.status(), response.headers())?;
+
+        let headers = response.headers().clone();
+        let jwks = self.limits.parse_jwks(&read_body_async(response, self.limits.max_jwks_size).await?)?;
+        Ok(self.share_async(self.store(jwks, &headers)).await)
+    }
+}
+
+/// Determine how long a response may be cached based on its
+/// `Cache-Control`, `Expires`, `Date` and `Age` headers. Returns
+/// `None` if the response does not specify a lifetime.
+///
+/// Lifetimes are limited to one year, as servers may specify
+/// arbitrarily large values.
+///
+/// See [RFC 7234, section
+/// 4.2.1](https://tools.ietf.org/html/rfc7234#section-4.2.1).
+pub(crate) fn cache_lifetime(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    response_lifetime(headers, now).map(|ttl| ttl.min(MAX_CACHE_LIFETIME))
+}
+
+/// Determine the point in time at which keys cached at `now` with the
+/// specified lifetime become stale.
+fn expiry(now: SystemTime, ttl: Duration) -> SystemTime {
+    let ttl = ttl.min(MAX_CACHE_LIFETIME);
+    now.checked_add(ttl).unwrap_or(now + MAX_CACHE_LIFETIME)
+}
+
+/// Determine the unbounded lifetime of a response, see
+/// `cache_lifetime`.
+fn response_lifetime(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+
+    if let Some(cache_control) = header(CACHE_CONTROL) {
+        let mut max_age = None;
+
+        for directive in cache_control.split(',').map(str::trim) {
+            let directive = directive.to_ascii_lowercase();
+
+            if directive == "no-cache" || directive == "no-store" {
+                return Some(Duration::from_secs(0));
+            }
+
+            if let Some(seconds) = directive.strip_prefix("max-age=") {
+                max_age = seconds.trim_matches('"').parse().ok();
+            }
+        }
+
+        if let Some(max_age) = max_age {
+            let age = header(AGE).and_then(|age| age.trim().parse().ok()).unwrap_or(0);
+            return Some(Duration::from_secs(u64::saturating_sub(max_age, age)));
+        }
+    }
+
+    let expires = header(EXPIRES)?;
+
+    // Invalid dates (commonly "0") denote responses that are already
+    // expired.
+    let expires = match httpdate::parse_http_date(expires) {
+        Ok(expires) => expires,
+        Err(_) => return Some(Duration::from_secs(0)),
+    };
+
+    // The lifetime is relative to the server's clock if it provided
+    // its current time in the response.
+    let date = header(DATE)
+        .and_then(|date| httpdate::parse_http_date(date).ok())
+        .unwrap_or(now);
+
+    Some(expires.duration_since(date).unwrap_or_default())
+}
+
+/// Handle to a background task that refreshes a `KeyStore`, created
+/// by `KeyStore::spawn_refresher`.
+///
+/// The task is stopped when `RefreshHandle::shutdown` is called or
+/// when the handle is dropped.
+#[cfg(feature = "tokio")]
+pub struct RefreshHandle {
+    shutdown: tokio::sync::oneshot::Sender<()>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+#[cfg(feature = "tokio")]
+impl RefreshHandle {
+    /// Stop the background refresh task and wait for it to finish.
+    pub async fn shutdown(self) {
+        // The task may already have finished if the runtime is
+        // shutting down, in which case there is nothing to stop.
+        let _ = self.shutdown.send(());
+        let _ = self.task.await;
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl KeyStore {
+    /// Spawn a task on the current tokio runtime that fetches the key
+    /// set immediately and then once per `interval`, so that requests
+    /// are served from the cache without waiting for a fetch.
+    ///
+    /// Fetched key sets replace the cached key set atomically. If a
+    /// fetch fails, the previously cached key set is retained. The
+    /// interval should be shorter than the lifetime of the key set as
+    /// specified by the JWKS response. Intervals shorter than 100
+    /// milliseconds (including zero) are raised to that minimum.
+    ///
+    /// This function panics if it is not called from within a tokio
+    /// runtime.
+    pub fn spawn_refresher(self: Arc<Self>, interval: Duration) -> RefreshHandle {
+        let (shutdown, mut stop) = tokio::sync::oneshot::channel();
+        let interval = interval.max(MIN_REFRESH_INTERVAL);
+
+        let task = tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+
+            loop {
+                tokio::select! {
+                    _ = ticks.tick() => {
+                        let _ = self.refresh_async().await;
+                    },
+
+                    // Either shutdown was requested or the handle was
+                    // dropped.
+                    _ = &mut stop => break,
+                }
+            }
+        });
+
+        RefreshHandle { shutdown, task }
+    }
+}
@@ -0,0 +1,179 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+//! Implements a validator that is initialised on first use, for
+//! services that can not perform initialisation before handling
+//! requests, such as serverless functions.
+
+use std::sync::{Arc, OnceLock};
+
+#[cfg(feature = "fetch")]
+use std::sync::{Mutex, PoisonError};
+
+use crate::{JWTResult, KeyStore, ValidJWT, Validation, Validator};
+
+/// Initialisation of a `StaticValidator`.
+enum Init {
+    /// Discover the key set of an issuer, and validate tokens using the
+    /// validations returned by the function.
+    Issuer(&'static str, fn() -> Vec<Validation>),
+
+    /// Construct the validator using the function.
+    Custom(fn() -> JWTResult<Validator>),
+}
+
+/// Validator that is constructed on first use and then reused by all
+/// later invocations, which is suitable for use in a `static` item.
+///
+/// In serverless functions (e.g. AWS Lambda), the discovery document
+/// and key set of the issuer are then fetched once per instance during
+/// its first invocation, and remain cached during warm invocations.
+///
+/// Concurrent first invocations wait for a single initialisation.
+/// Failed initialisations are not cached, so the next invocation tries
+/// again.
+///
+/// ```rust,no_run
+/// # use alcoholic_jwt::{StaticValidator, Validation};
+/// static VALIDATOR: StaticValidator = StaticValidator::from_issuer(
+///     "https://auth.test.aprila.no",
+///     || vec![Validation::Audience("my-api".into()), Validation::NotExpired],
+/// );
+///
+/// # fn handler(token: &str) {
+/// // ... in the function handler:
+/// # #[cfg(feature = "fetch")]
+/// let result = VALIDATOR.validate(token);
+/// # }
+/// ```
+pub struct StaticValidator {
+    init: Init,
+    validator: OnceLock<Validator>,
+
+    #[cfg(feature = "fetch")]
+    lock: Mutex<()>,
+
+    #[cfg(feature = "async")]
+    async_lock: tokio::sync::Mutex<()>,
+}
+
+impl StaticValidator {
+    /// Create a validator for tokens of an OpenID Connect issuer.
+    ///
+    /// On first use, the discovery document and the key set of the
+    /// issuer are fetched (see `KeyStore::from_issuer`) and tokens are
+    /// validated using the validations returned by the function, as
+    /// well as a validation of their issuer.
+    pub const fn from_issuer(issuer: &'static str, validations: fn() -> Vec<Validation>) -> StaticValidator {
+        StaticValidator::with_init(Init::Issuer(issuer, validations))
+    }
+
+    /// Create a validator that is constructed by the specified function
+    /// on first use.
+    ///
+    /// The function is also called by `get_async`, so it must not block
+    /// if the validator is used asynchronously.
+    pub const fn new(init: fn() -> JWTResult<Validator>) -> StaticValidator {
+        StaticValidator::with_init(Init::Custom(init))
+    }
+
+    const fn with_init(init: Init) -> StaticValidator {
+        StaticValidator {
+            init,
+            validator: OnceLock::new(),
+
+            #[cfg(feature = "fetch")]
+            lock: Mutex::new(()),
+
+            #[cfg(feature = "async")]
+            async_lock: tokio::sync::Mutex::const_new(()),
+        }
+    }
+
+    /// Return the validator, constructing it using blocking HTTP
+    /// requests if this is its first use.
+    ///
+    /// This function must not be called from within an asynchronous
+    /// runtime, use `get_async` instead.
+    #[cfg(feature = "fetch")]
+    pub fn get(&self) -> JWTResult<&Validator> {
+        if let Some(validator) = self.validator.get() {
+            return Ok(validator);
+        }
+
+        // Concurrent callers wait for the first one to finish, and use
+        // its validator if it succeeded.
+        let _guard = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(validator) = self.validator.get() {
+            return Ok(validator);
+        }
+
+        let validator = match self.init {
+            Init::Issuer(issuer, validations) => {
+                let store = KeyStore::from_issuer(issuer)?;
+                store.keys()?;
+                issuer_validator(store, validations)
+            },
+            Init::Custom(init) => init()?,
+        };
+
+        Ok(self.validator.get_or_init(|| validator))
+    }
+
+    /// Validate a token, constructing the validator if this is its
+    /// first use. See `get` and `Validator::validate`.
+    #[cfg(feature = "fetch")]
+    pub fn validate(&self, token: &str) -> JWTResult<ValidJWT> {
+        self.get()?.validate(token)
+    }
+
+    /// Asynchronous equivalent of `StaticValidator::get`.
+    #[cfg(feature = "async")]
+    pub async fn get_async(&self) -> JWTResult<&Validator> {
+        if let Some(validator) = self.validator.get() {
+            return Ok(validator);
+        }
+
+        let _guard = self.async_lock.lock().await;
+        if let Some(validator) = self.validator.get() {
+            return Ok(validator);
+        }
+
+        let validator = match self.init {
+            Init::Issuer(issuer, validations) => {
+                let store = KeyStore::from_issuer_async(issuer).await?;
+                store.keys_async().await?;
+                issuer_validator(store, validations)
+            },
+            Init::Custom(init) => init()?,
+        };
+
+        Ok(self.validator.get_or_init(|| validator))
+    }
+
+    /// Asynchronous equivalent of `StaticValidator::validate`.
+    #[cfg(feature = "async")]
+    pub async fn validate_async(&self, token: &str) -> JWTResult<ValidJWT> {
+        self.get_async().await?.validate_async(token).await
+    }
+}
+
+/// Construct the validator for tokens of a discovered issuer.
+fn issuer_validator(store: KeyStore, validations: fn() -> Vec<Validation>) -> Validator {
+    let mut validations = validations();
+    validations.extend(store.issuer_validation());
+    Validator::new(validations).key_provider(Arc::new(store))
+}
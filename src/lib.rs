@@ -67,12 +67,47 @@
 //!
 //! [JWKS]: https://tools.ietf.org/html/rfc7517
 
+// Type names in this library follow the capitalisation used in the
+// JOSE specifications (JWK, JWKS, RSA, ...).
+#![allow(clippy::upper_case_acronyms)]
+
 #[macro_use] extern crate serde_derive;
 
 extern crate base64;
 extern crate openssl;
 extern crate serde;
 extern crate serde_json;
+extern crate zeroize;
+
+#[cfg(any(feature = "fetch", feature = "async"))]
+extern crate httpdate;
+
+#[cfg(any(feature = "fetch", feature = "async"))]
+extern crate reqwest;
+
+#[cfg(feature = "async")]
+extern crate tokio;
+
+#[cfg(feature = "redis")]
+extern crate redis;
+
+#[cfg(feature = "regex")]
+extern crate regex;
+
+#[cfg(feature = "rayon")]
+extern crate rayon;
+
+#[cfg(feature = "actix")]
+extern crate actix_web;
+
+#[cfg(feature = "tracing")]
+extern crate tracing;
+
+#[cfg(feature = "prometheus")]
+extern crate prometheus;
+
+#[cfg(feature = "otel")]
+extern crate opentelemetry;
 
 use base64::{URL_SAFE_NO_PAD, Config, DecodeError};
 use openssl::bn::BigNum;
@@ -81,11 +116,120 @@ use openssl::hash::MessageDigest;
 use openssl::pkey::{Public, PKey};
 use openssl::rsa::Rsa;
 use openssl::sign::Verifier;
-use serde::de::DeserializeOwned;
-use serde_json::Value;
+use serde::de::{Deserialize, DeserializeOwned, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, OnceLock};
 use std::time::{UNIX_EPOCH, Duration, SystemTime};
 
+#[cfg(any(feature = "fetch", feature = "async"))]
+mod cache;
+
+#[cfg(any(feature = "fetch", feature = "async"))]
+mod fetch;
+
+mod audit;
+mod auth;
+mod batch;
+mod clock;
+mod detached;
+mod dpop;
+mod filestore;
+
+#[cfg(any(feature = "fetch", feature = "async"))]
+mod introspection;
+
+mod jwe;
+mod jws;
+
+#[cfg(any(feature = "fetch", feature = "async"))]
+mod keystore;
+
+#[cfg(any(feature = "fetch", feature = "async"))]
+mod lazy;
+
+mod metrics;
+mod presets;
+mod provider;
+mod replay;
+mod sdjwt;
+mod signing;
+mod tokencache;
+mod validator;
+
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
+
+#[cfg(any(feature = "fetch", feature = "async"))]
+pub mod providers;
+
+#[cfg(feature = "actix")]
+pub mod actix;
+
+#[cfg(feature = "axum")]
+pub mod axum;
+
+#[cfg(feature = "tower")]
+pub mod tower;
+
+#[cfg(feature = "rocket")]
+pub mod rocket;
+
+#[cfg(feature = "warp")]
+pub mod warp;
+
+#[cfg(feature = "tonic")]
+pub mod tonic;
+
+#[cfg(feature = "otel")]
+pub mod otel;
+
+pub use crate::audit::{AuditDecision, AuditEvent, AuditHook};
+pub use crate::auth::AuthError;
+pub use crate::batch::validate_batch;
+pub use crate::clock::{Clock, FixedClock, SystemClock};
+pub use crate::detached::validate_detached;
+pub use crate::dpop::{access_token_hash, validate_dpop_proof, DPoPProof};
+pub use crate::filestore::FileKeyStore;
+pub use crate::jwe::{decrypt_jwe, validate_nested, DecryptedJWE};
+pub use crate::jws::{validate_jws_json, validate_jws_json_with_policy, JWSJson, JWSSignature, SignaturePolicy};
+pub use crate::metrics::{MetricsCache, MetricsSink};
+pub use crate::provider::{BoxFuture, KeyProvider};
+pub use crate::replay::{InMemoryReplayCache, ReplayCache};
+pub use crate::sdjwt::{validate_sd_jwt, Disclosure, SDJWT};
+pub use crate::signing::{client_assertion, generate_rsa, sign, PrivateJWK};
+pub use crate::tokencache::TokenCache;
+pub use crate::validator::Validator;
+
+#[cfg(any(feature = "fetch", feature = "async"))]
+pub use crate::introspection::IntrospectionClient;
+
+#[cfg(any(feature = "fetch", feature = "async"))]
+pub use crate::keystore::KeyStore;
+
+#[cfg(any(feature = "fetch", feature = "async"))]
+pub use crate::lazy::StaticValidator;
+
+#[cfg(any(feature = "fetch", feature = "async"))]
+pub use crate::cache::{CacheBackend, CacheEntry};
+
+#[cfg(all(feature = "redis", any(feature = "fetch", feature = "async")))]
+pub use crate::cache::RedisCache;
+
+#[cfg(feature = "tokio")]
+pub use crate::keystore::RefreshHandle;
+
+#[cfg(feature = "prometheus")]
+pub use crate::metrics::PrometheusMetrics;
+
+// The original tests predate some of the lints of newer clippy
+// versions and are kept as they are.
 #[cfg(test)]
+#[allow(clippy::needless_borrow, clippy::useless_conversion)]
 mod tests;
 
 /// URL-safe character set without padding that allows trailing bits,
@@ -100,33 +244,95 @@ fn jwt_forgiving() -> Config {
 
 /// JWT algorithm used. The only supported algorithm is currently
 /// RS256.
-#[derive(Clone, Deserialize, Debug)]
-enum KeyAlgorithm { RS256 }
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum KeyAlgorithm { RS256 }
+
+impl fmt::Display for KeyAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            KeyAlgorithm::RS256 => write!(f, "RS256"),
+        }
+    }
+}
 
 /// Type of key contained in a JWT. The only supported key type is
 /// currently RSA.
-#[derive(Clone, Deserialize, Debug)]
-enum KeyType { RSA }
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum KeyType { RSA }
 
 /// Representation of a single JSON Web Key. See [RFC
 /// 7517](https://tools.ietf.org/html/rfc7517#section-4).
-#[allow(dead_code)] // kty & alg only constrain deserialisation, but aren't used
-#[derive(Clone, Debug, Deserialize)]
+///
+/// Members of the key that are not used by this library are retained
+/// and written back out when the key is serialised.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct JWK {
     kty: KeyType,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
     alg: Option<KeyAlgorithm>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
     kid: Option<String>,
 
+    // Intended use of the key ("sig" or "enc")
+    #[serde(rename = "use", skip_serializing_if = "Option::is_none")]
+    key_use: Option<String>,
+
+    // Operations the key is intended for (e.g. "verify")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key_ops: Option<Vec<String>>,
+
     // Shared modulus
     n: String,
 
     // Public key exponent
     e: String,
+
+    // Any other members of the key
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+
+    // OpenSSL key constructed from the key fragments
+    #[serde(skip)]
+    key: KeyCache,
+}
+
+/// Lazily constructed OpenSSL key of a JWK, which avoids decoding the
+/// key fragments for every validated token.
+///
+/// Clones of a JWK share the cache, so keys handed out by key stores
+/// only need to be constructed once.
+#[derive(Clone, Default)]
+struct KeyCache(Arc<OnceLock<PKey<Public>>>);
+
+impl fmt::Debug for KeyCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("KeyCache")
+    }
+}
+
+// The cache is derived from the key fragments and thus irrelevant for
+// comparisons.
+impl PartialEq for KeyCache {
+    fn eq(&self, _: &KeyCache) -> bool {
+        true
+    }
 }
 
 /// Representation of a set of JSON Web Keys. See [RFC
 /// 7517](https://tools.ietf.org/html/rfc7517#section-5).
-#[derive(Clone, Debug, Deserialize)]
+///
+/// As with `JWK`, unknown members of the key set are retained when
+/// serialising.
+///
+/// Deserialisation of a key set is lenient: keys that can not be
+/// represented by this library (for example EC or symmetric keys) are
+/// skipped instead of failing the entire set. Such keys are collected
+/// and can be inspected using `JWKS::rejected`.
+#[derive(Clone, Debug, PartialEq)]
 pub struct JWKS {
     // This is a vector instead of some kind of map-like structure
     // because key IDs are in fact optional.
@@ -135,12 +341,430 @@ pub struct JWKS {
     // violate the JWKS-definition either, but behaviour in that case
     // is unspecified.
     keys: Vec<JWK>,
+
+    // Keys that could not be deserialised as a supported JWK
+    rejected: Vec<Value>,
+
+    extra: Map<String, Value>,
+
+    // Positions of the keys in `keys` by their key ID, which makes
+    // lookups constant-time for large key sets.
+    index: HashMap<String, Vec<usize>>,
+}
+
+/// Serialisation format of a key set. Keys are deserialised
+/// individually to allow unsupported keys to be skipped.
+#[derive(Serialize, Deserialize)]
+struct RawJWKS<K, X> {
+    keys: Vec<K>,
+
+    #[serde(flatten)]
+    extra: X,
+}
+
+/// Reference to either a supported or a rejected key, used when
+/// serialising a key set.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum KeyRef<'a> {
+    Supported(&'a JWK),
+    Rejected(&'a Value),
+}
+
+impl<'de> Deserialize<'de> for JWKS {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de> {
+        let raw = RawJWKS::<Value, Map<String, Value>>::deserialize(deserializer)?;
+        let mut keys = Vec::with_capacity(raw.keys.len());
+        let mut rejected = vec![];
+
+        for key in raw.keys {
+            match JWK::deserialize(&key) {
+                Ok(jwk) => keys.push(jwk),
+                Err(_) => rejected.push(key),
+            }
+        }
+
+        let mut jwks = JWKS { keys, rejected, extra: raw.extra, index: HashMap::new() };
+        jwks.reindex();
+
+        Ok(jwks)
+    }
+}
+
+impl Serialize for JWKS {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer {
+        let keys = self.keys.iter().map(KeyRef::Supported)
+            .chain(self.rejected.iter().map(KeyRef::Rejected))
+            .collect();
+
+        RawJWKS { keys, extra: &self.extra }.serialize(serializer)
+    }
+}
+
+impl JWK {
+    /// Construct an RSA JWK from its base64-URL encoded modulus
+    /// (`n`) and public exponent (`e`), for example if the key
+    /// material was obtained from a source other than a JWKS.
+    ///
+    /// The key fragments are not decoded until the key is used.
+    pub fn new_rsa(n: &str, e: &str, kid: Option<String>) -> JWK {
+        JWK {
+            kty: KeyType::RSA,
+            alg: None,
+            kid,
+            key_use: None,
+            key_ops: None,
+            n: n.into(),
+            e: e.into(),
+            extra: Map::new(),
+            key: KeyCache::default(),
+        }
+    }
+
+    /// Key ID of this JWK, if present.
+    pub fn kid(&self) -> Option<&str> {
+        self.kid.as_deref()
+    }
+
+    /// Algorithm this JWK is intended to be used with, if present.
+    pub fn alg(&self) -> Option<KeyAlgorithm> {
+        self.alg
+    }
+
+    /// Type of this JWK.
+    pub fn kty(&self) -> KeyType {
+        self.kty
+    }
+
+    /// Intended use of this JWK (`"sig"` or `"enc"`), if present.
+    pub fn key_use(&self) -> Option<&str> {
+        self.key_use.as_deref()
+    }
+
+    /// Operations this JWK is intended for, if present.
+    pub fn key_ops(&self) -> Option<&[String]> {
+        self.key_ops.as_deref()
+    }
+
+    /// Check whether this JWK may be used to verify signatures, based
+    /// on its `use` and `key_ops` members. Keys that specify neither
+    /// are assumed to be usable for verification. See [RFC 7517,
+    /// section 4.3](https://tools.ietf.org/html/rfc7517#section-4.3).
+    pub fn is_signing_key(&self) -> bool {
+        let use_ok = match self.key_use {
+            None => true,
+            Some(ref key_use) => key_use == "sig",
+        };
+
+        let ops_ok = match self.key_ops {
+            None => true,
+            Some(ref ops) => ops.iter().any(|op| op == "verify"),
+        };
+
+        use_ok && ops_ok
+    }
+
+    /// Construct the OpenSSL public key represented by this JWK.
+    ///
+    /// This can be used to hand the key material fetched from a JWKS
+    /// to other libraries that operate on OpenSSL keys directly.
+    pub fn to_pkey(&self) -> JWTResult<PKey<Public>> {
+        self.public_key().cloned()
+    }
+
+    /// Return the OpenSSL public key represented by this JWK, which is
+    /// constructed on first use.
+    fn public_key(&self) -> JWTResult<&PKey<Public>> {
+        if let Some(key) = self.key.0.get() {
+            return Ok(key);
+        }
+
+        // Concurrent callers may construct the key simultaneously, in
+        // which case the first key to be stored wins.
+        let key = PKey::from_rsa(public_key_from_jwk(self)?)?;
+        Ok(self.key.0.get_or_init(|| key))
+    }
+
+    /// Encode the public key represented by this JWK as a PEM-encoded
+    /// `SubjectPublicKeyInfo` structure (`-----BEGIN PUBLIC KEY-----`).
+    pub fn to_pem(&self) -> JWTResult<Vec<u8>> {
+        self.to_pkey()?.public_key_to_pem().map_err(Into::into)
+    }
+
+    /// Compute the JWK thumbprint of this key as specified in [RFC
+    /// 7638](https://tools.ietf.org/html/rfc7638), i.e. the
+    /// base64url-encoded SHA-256 hash of its required members.
+    pub fn thumbprint(&self) -> String {
+        // The required members of RSA keys, in lexicographic order
+        // and without whitespace.
+        let json = format!("{{\"e\":{},\"kty\":\"RSA\",\"n\":{}}}",
+                           Value::from(self.e.as_str()), Value::from(self.n.as_str()));
+
+        base64::encode_config(&openssl::sha::sha256(json.as_bytes()), base64::URL_SAFE_NO_PAD)
+    }
+}
+
+/// Policy for handling keys with a key ID that is already present
+/// when merging key sets using `JWKS::merge`. Keys without a key ID
+/// are always retained.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    /// Keep the keys already present in the set and skip incoming
+    /// keys with the same key ID.
+    KeepExisting,
+
+    /// Replace keys already present in the set with the incoming keys
+    /// that have the same key ID.
+    Replace,
+
+    /// Keep both the existing and the incoming keys. Use
+    /// `JWKS::find_all` and `validate_any` to validate against such a
+    /// set.
+    KeepBoth,
+}
+
+/// Minimum requirements on the strength of keys used for signature
+/// verification.
+///
+/// The default policy requires RSA keys to have a modulus of at least
+/// 2048 bits, as recommended by [NIST SP
+/// 800-57](https://csrc.nist.gov/publications/detail/sp/800-57-part-1/rev-5/final).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyPolicy {
+    /// Minimum size of the modulus of RSA keys, in bits.
+    pub min_rsa_bits: u32,
+}
+
+impl Default for KeyPolicy {
+    fn default() -> Self {
+        KeyPolicy { min_rsa_bits: 2048 }
+    }
+}
+
+impl KeyPolicy {
+    /// Check whether a JWK satisfies this policy.
+    ///
+    /// Returns `ValidationError::WeakKey` if the key is too weak, or
+    /// `ValidationError::InvalidJWK` if the key can not be decoded.
+    pub fn check(&self, jwk: &JWK) -> JWTResult<()> {
+        let bits = public_key_from_jwk(jwk)?.n().num_bits() as u32;
+
+        if bits < self.min_rsa_bits {
+            Err(ValidationError::WeakKey(bits))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Limits on the size of untrusted input, which protect against
+/// excessive resource usage when decoding attacker-supplied tokens
+/// and key sets.
+///
+/// The default limits are generous enough for the tokens and key sets
+/// issued by common identity providers. Limits on tokens can be
+/// changed using `Validation::Limits` or `Validator::limits`, limits
+/// on key sets using the `limits` setting of the key stores.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum length of an encoded token, in bytes.
+    pub max_token_length: usize,
+
+    /// Maximum size of the decoded header and claims of a token, in
+    /// bytes.
+    pub max_part_size: usize,
+
+    /// Maximum size of a key set document, in bytes.
+    pub max_jwks_size: usize,
+
+    /// Maximum number of keys in a key set document, including keys
+    /// that are not supported by this library.
+    pub max_jwks_keys: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_token_length: 64 * 1024,
+            max_part_size: 48 * 1024,
+            max_jwks_size: 1024 * 1024,
+            max_jwks_keys: 100,
+        }
+    }
+}
+
+impl Limits {
+    /// Check that a token does not exceed these limits, before any of
+    /// its components are decoded.
+    ///
+    /// Returns `ValidationError::LimitExceeded` naming the exceeded
+    /// limit otherwise.
+    pub fn check_token(&self, token: &str) -> JWTResult<()> {
+        if token.len() > self.max_token_length {
+            return Err(ValidationError::LimitExceeded("max_token_length"));
+        }
+
+        // The decoded size of a base64-encoded part is at most three
+        // quarters of its encoded size.
+        let too_large = token.splitn(3, '.')
+            .take(2)
+            .any(|part| part.len() * 3 / 4 > self.max_part_size);
+
+        if too_large {
+            return Err(ValidationError::LimitExceeded("max_part_size"));
+        }
+
+        Ok(())
+    }
+
+    /// Decode a key set document, if it does not exceed these limits.
+    pub fn parse_jwks(&self, json: &[u8]) -> JWTResult<JWKS> {
+        if json.len() > self.max_jwks_size {
+            return Err(ValidationError::LimitExceeded("max_jwks_size"));
+        }
+
+        let jwks: JWKS = serde_json::from_slice(json)?;
+        if jwks.len() + jwks.rejected().len() > self.max_jwks_keys {
+            return Err(ValidationError::LimitExceeded("max_jwks_keys"));
+        }
+
+        Ok(jwks)
+    }
 }
 
 impl JWKS {
+    /// All keys contained in this set, in the order in which they
+    /// appear in the JWKS document.
+    pub fn keys(&self) -> &[JWK] {
+        &self.keys
+    }
+
+    /// Number of keys contained in this set.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Check whether this set contains no keys.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Keys in the JWKS document that were skipped during
+    /// deserialisation because they are not supported by this
+    /// library, in their original JSON representation.
+    pub fn rejected(&self) -> &[Value] {
+        &self.rejected
+    }
+
+    /// Merge the keys of another key set into this one, for example
+    /// to combine the key sets of several trusted issuers. Keys whose
+    /// key ID is already present are handled according to `policy`.
+    pub fn merge(&mut self, other: JWKS, policy: DuplicateKeys) {
+        let mut incoming = other.keys;
+
+        match policy {
+            DuplicateKeys::KeepBoth => (),
+
+            DuplicateKeys::KeepExisting => {
+                let existing = &self.index;
+                incoming.retain(|jwk| match jwk.kid {
+                    None => true,
+                    Some(ref kid) => !existing.contains_key(kid),
+                });
+            },
+
+            DuplicateKeys::Replace => {
+                let replaced = &other.index;
+                self.keys.retain(|jwk| match jwk.kid {
+                    None => true,
+                    Some(ref kid) => !replaced.contains_key(kid),
+                });
+            },
+        }
+
+        self.keys.extend(incoming);
+        self.rejected.extend(other.rejected);
+        self.reindex();
+    }
+
+    /// Remove all keys that do not satisfy the specified key policy
+    /// from this set. Removed keys are added to the rejected keys.
+    pub fn apply_policy(&mut self, policy: &KeyPolicy) {
+        let (keys, weak): (Vec<JWK>, Vec<JWK>) = self.keys.drain(..)
+            .partition(|jwk| policy.check(jwk).is_ok());
+
+        self.keys = keys;
+        self.rejected.extend(weak.iter().filter_map(|jwk| serde_json::to_value(jwk).ok()));
+        self.reindex();
+    }
+
     /// Attempt to find a JWK by its key ID.
     pub fn find(&self, kid: &str) -> Option<&JWK> {
-        self.keys.iter().find(|jwk| jwk.kid == Some(kid.into()))
+        self.find_all(kid).next()
+    }
+
+    /// Find all JWKs with the specified key ID. Providers may publish
+    /// several keys with the same key ID while rotating keys, in
+    /// which case all of them can be passed to `validate_any`.
+    pub fn find_all(&self, kid: &str) -> impl Iterator<Item = &JWK> + '_ {
+        self.index.get(kid)
+            .into_iter()
+            .flatten()
+            .map(move |&idx| &self.keys[idx])
+    }
+
+    /// Attempt to find a JWK by its key ID, skipping keys that are
+    /// not intended for signature verification (for example keys with
+    /// `"use": "enc"`).
+    pub fn find_signing_key(&self, kid: &str) -> Option<&JWK> {
+        self.find_all(kid).find(|jwk| jwk.is_signing_key())
+    }
+
+    /// Rebuild the key ID index after the key vector has changed.
+    fn reindex(&mut self) {
+        self.index.clear();
+
+        for (idx, jwk) in self.keys.iter().enumerate() {
+            if let Some(ref kid) = jwk.kid {
+                self.index.entry(kid.clone()).or_default().push(idx);
+            }
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a JWKS {
+    type Item = &'a JWK;
+    type IntoIter = std::slice::Iter<'a, JWK>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.keys.iter()
+    }
+}
+
+impl IntoIterator for JWKS {
+    type Item = JWK;
+    type IntoIter = std::vec::IntoIter<JWK>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.keys.into_iter()
+    }
+}
+
+/// Construct a key set from individual keys, e.g. for publishing
+/// generated keys.
+impl std::iter::FromIterator<JWK> for JWKS {
+    fn from_iter<I: IntoIterator<Item = JWK>>(keys: I) -> JWKS {
+        let mut jwks = JWKS {
+            keys: keys.into_iter().collect(),
+            rejected: vec![],
+            extra: Map::new(),
+            index: HashMap::new(),
+        };
+
+        jwks.reindex();
+        jwks
     }
 }
 
@@ -153,6 +777,11 @@ struct JWT<'a> (&'a str);
 /// Specific claim fields are only decoded internally in the library
 /// for validation purposes, while it is generally up to the consumer
 /// of the validated JWT what structure they would like to impose.
+///
+/// Validated tokens can be serialised (e.g. for audit logs) as an
+/// object with `headers` and `claims` members. They intentionally can
+/// not be deserialised, as that would bypass validation.
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub struct ValidJWT {
     /// JOSE header of the JSON Web Token. Certain fields are
     /// guaranteed to be present in this header, consult section 5 of
@@ -166,28 +795,661 @@ pub struct ValidJWT {
     pub claims: Value,
 }
 
+impl ValidJWT {
+    /// Deserialize the claims of the token into the specified type.
+    pub fn claims_as<T: DeserializeOwned>(&self) -> JWTResult<T> {
+        T::deserialize(&self.claims).map_err(Into::into)
+    }
+
+    /// Return the claims of the token as a map from claim names to
+    /// values, e.g. for attaching them to request extensions.
+    pub fn claims_map(&self) -> HashMap<String, Value> {
+        match self.claims {
+            Value::Object(ref claims) => claims.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+
+            // Claim sets of validated tokens are always objects.
+            _ => HashMap::new(),
+        }
+    }
+
+    /// Deserialize the header of the token into the specified type.
+    pub fn headers_as<T: DeserializeOwned>(&self) -> JWTResult<T> {
+        T::deserialize(&self.headers).map_err(Into::into)
+    }
+
+    /// Deserialize a single claim of the token into the specified
+    /// type. Returns `None` if the claim is missing or `null`.
+    pub fn claim<T: DeserializeOwned>(&self, name: &str) -> JWTResult<Option<T>> {
+        match self.claims.get(name) {
+            None | Some(Value::Null) => Ok(None),
+            Some(value) => T::deserialize(value).map(Some).map_err(Into::into),
+        }
+    }
+
+    /// Decode the registered claims of the token.
+    pub fn registered(&self) -> JWTResult<RegisteredClaims> {
+        self.claims_as()
+    }
+
+    /// Return the expiry time of the token, see
+    /// `RegisteredClaims::expires_at`.
+    pub fn expires_at<T: From<SystemTime>>(&self) -> Option<T> {
+        self.registered().ok()?.expires_at()
+    }
+
+    /// Return the time at which the token was issued, see
+    /// `RegisteredClaims::expires_at`.
+    pub fn issued_at<T: From<SystemTime>>(&self) -> Option<T> {
+        self.registered().ok()?.issued_at()
+    }
+
+    /// Return the time before which the token must not be accepted,
+    /// see `RegisteredClaims::expires_at`.
+    pub fn not_before<T: From<SystemTime>>(&self) -> Option<T> {
+        self.registered().ok()?.not_before()
+    }
+}
+
+/// The registered claims of a JSON Web Token, see [RFC 7519, section
+/// 4.1](https://tools.ietf.org/html/rfc7519#section-4.1).
+///
+/// Times are specified in seconds since the UNIX epoch. Fractional
+/// times are truncated to full seconds.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RegisteredClaims {
+    /// Issuer of the token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+
+    /// Subject of the token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+
+    /// Intended audience(s) of the token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aud: Option<Audience>,
+
+    /// Expiry time of the token.
+    #[serde(default, deserialize_with = "deserialize_numeric_date", skip_serializing_if = "Option::is_none")]
+    pub exp: Option<u64>,
+
+    /// Time before which the token must not be accepted.
+    #[serde(default, deserialize_with = "deserialize_numeric_date", skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<u64>,
+
+    /// Time at which the token was issued.
+    #[serde(default, deserialize_with = "deserialize_numeric_date", skip_serializing_if = "Option::is_none")]
+    pub iat: Option<u64>,
+
+    /// Unique identifier of the token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+}
+
+impl RegisteredClaims {
+    /// Return the expiry time of the token.
+    ///
+    /// The time can be returned as any type that can be converted from
+    /// a `SystemTime`, such as `time::OffsetDateTime` or
+    /// `chrono::DateTime<Utc>`. Times that can not be represented as a
+    /// `SystemTime` are returned as `None`.
+    pub fn expires_at<T: From<SystemTime>>(&self) -> Option<T> {
+        self.exp.and_then(epoch_time).map(T::from)
+    }
+
+    /// Return the time at which the token was issued, see
+    /// `RegisteredClaims::expires_at`.
+    pub fn issued_at<T: From<SystemTime>>(&self) -> Option<T> {
+        self.iat.and_then(epoch_time).map(T::from)
+    }
+
+    /// Return the time before which the token must not be accepted,
+    /// see `RegisteredClaims::expires_at`.
+    pub fn not_before<T: From<SystemTime>>(&self) -> Option<T> {
+        self.nbf.and_then(epoch_time).map(T::from)
+    }
+}
+
+/// Convert a NumericDate to a `SystemTime`, unless it is too far in
+/// the future to be represented.
+fn epoch_time(secs: u64) -> Option<SystemTime> {
+    UNIX_EPOCH.checked_add(Duration::from_secs(secs))
+}
+
+/// Deserialize a NumericDate (RFC 7519, section 2), which may be an
+/// integer or a floating point number, into full seconds.
+fn deserialize_numeric_date<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    let value: Option<f64> = Option::deserialize(deserializer)?;
+    Ok(value.map(|value| value as u64))
+}
+
+/// Value of an "aud"-claim, which can contain either a single audience
+/// or an array of audiences.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Audience {
+    Single(String),
+    Multi(Vec<String>)
+}
+
+impl Audience {
+    /// Check whether the claim contains the specified audience.
+    pub fn contains(&self, aud: &str) -> bool {
+        match *self {
+            Audience::Single(ref claim) => claim == aud,
+            Audience::Multi(ref claims) => claims.iter().any(|claim| claim == aud),
+        }
+    }
+
+    /// Return all audiences contained in the claim.
+    pub fn values(&self) -> Vec<String> {
+        match *self {
+            Audience::Single(ref claim) => vec![claim.clone()],
+            Audience::Multi(ref claims) => claims.clone(),
+        }
+    }
+}
+
+/// Typed representation of the registered members of a JOSE header.
+/// See [RFC 7515, section 4.1](https://tools.ietf.org/html/rfc7515#section-4.1).
+///
+/// Other members of the header are retained in `extra`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JoseHeader {
+    /// Signature algorithm of the token, e.g. `RS256`.
+    pub alg: String,
+
+    /// ID of the key used for signing the token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kid: Option<String>,
+
+    /// Media type of the token, e.g. `JWT` or `at+jwt`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub typ: Option<String>,
+
+    /// Content type of the payload, e.g. `JWT` for nested tokens.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cty: Option<String>,
+
+    /// SHA-1 thumbprint of the certificate of the signing key.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x5t: Option<String>,
+
+    /// SHA-256 thumbprint of the certificate of the signing key.
+    #[serde(rename = "x5t#S256", skip_serializing_if = "Option::is_none")]
+    pub x5t_s256: Option<String>,
+
+    /// Header members that must be understood by the recipient.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crit: Option<Vec<String>>,
+
+    /// Any other members of the header.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Representation of a decoded JSON Web Token whose signature has
+/// *not* been verified, as returned by `dangerous_decode_unverified`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnverifiedJWT {
+    /// JOSE header of the JSON Web Token.
+    pub headers: Value,
+
+    /// Claims contained in the JSON Web Token.
+    pub claims: Value,
+}
+
 /// Possible token claim validations. This enumeration only covers
 /// common use-cases, for other types of validations the user is
 /// encouraged to inspect the claim set manually.
+///
+/// New validations are added over time, so the enumeration is marked
+/// as non-exhaustive. The constructor functions (e.g.
+/// `Validation::issuer`) accept any convertible arguments.
+#[derive(Clone)]
+#[non_exhaustive]
 pub enum Validation {
     /// Validate that the issuer ("iss") claim matches a specified
     /// value.
     Issuer(String),
 
+    /// Validate that the issuer ("iss") claim matches one of the
+    /// specified values, for services that trust several issuers.
+    OneOfIssuers(Vec<String>),
+
+    /// Validate that the issuer ("iss") claim matches a template in
+    /// which placeholders of the form `{claim}` are replaced with the
+    /// values of the respective (string) claims of the token, e.g.
+    /// `https://login.microsoftonline.com/{tid}/v2.0` for multi-tenant
+    /// Azure AD applications.
+    IssuerTemplate(String),
+
     /// Validate that the audience ("aud") claim matches a specified
-    /// value.
+    /// value. If the claim is an array of audiences, the specified
+    /// value must be one of its members.
     Audience(String),
 
+    /// Validate that the audience ("aud") claim matches at least one
+    /// of the specified values, e.g. for services that are reachable
+    /// under several names.
+    OneOfAudiences(Vec<String>),
+
+    /// Validate that the authorized party ("azp"-claim), if present,
+    /// matches the specified client ID. See [OpenID Connect Core,
+    /// section 2](https://openid.net/specs/openid-connect-core-1_0.html#IDToken).
+    AuthorizedParty(String),
+
+    /// Validate that the "nonce"-claim matches the nonce sent in the
+    /// authentication request, as required for ID tokens obtained
+    /// through the implicit and hybrid flows of OpenID Connect.
+    Nonce(String),
+
+    /// Validate that the "at_hash"-claim of an ID token matches the
+    /// specified access token, see `oidc_token_hash`.
+    AccessTokenHash(String),
+
+    /// Validate that the "c_hash"-claim of an ID token matches the
+    /// specified authorization code, see `oidc_token_hash`.
+    CodeHash(String),
+
+    /// Validate that the authentication context class reference
+    /// ("acr"-claim) is one of the specified values.
+    OneOfAcrValues(Vec<String>),
+
+    /// Validate that the authentication methods references
+    /// ("amr"-claim) contain the specified method, e.g. `mfa`.
+    AmrContains(String),
+
     /// Validate that a subject value is present.
     SubjectPresent,
 
+    /// Validate that all claims with the specified names are present
+    /// and not `null`.
+    ClaimsPresent(Vec<String>),
+
+    /// Validate that at least one of the claims with the specified
+    /// names is present and not `null`, e.g. "sub" or "sid" in logout
+    /// tokens. Fails as if the first claim were missing otherwise.
+    AnyClaimPresent(Vec<String>),
+
+    /// Validate that the claim with the specified name is not present,
+    /// e.g. "nonce" in logout tokens.
+    ClaimAbsent(String),
+
+    /// Validate that the token grants the specified OAuth scope. Both
+    /// the space-delimited "scope"-claim of RFC 8693 and the "scp"
+    /// array used by some providers are supported.
+    HasScope(String),
+
     /// Validate that the expiry time of the token ("exp"-claim) has
-    /// not yet been reached.
+    /// not yet been reached. Expiry is only checked if this validation
+    /// is requested.
     NotExpired,
+
+    /// Validate that the not-before time of the token ("nbf"-claim)
+    /// has been reached. Tokens without an "nbf"-claim are accepted.
+    NotBeforeOk,
+
+    /// Validate that the token was issued ("iat"-claim) no longer ago
+    /// than the specified maximum age, regardless of its expiry time.
+    IssuedAtMaxAge(Duration),
+
+    /// Validate that the claim with the specified name is present and
+    /// has exactly the specified JSON value, e.g. for non-registered
+    /// claims such as a tenant ID.
+    ClaimEquals(String, Value),
+
+    /// Validate that the value at the specified JSON pointer (RFC
+    /// 6901) into the claim set, e.g. `/resource_access/api/roles/0`,
+    /// is present and equal to the specified value.
+    PointerEquals(String, Value),
+
+    /// Validate that the value at the specified JSON pointer into the
+    /// claim set is an array containing the specified value.
+    PointerContains(String, Value),
+
+    /// Validate that a Keycloak token grants the specified realm role
+    /// (listed in "realm_access.roles").
+    KeycloakRealmRole(String),
+
+    /// Validate that a Keycloak token grants the specified role of the
+    /// specified client (listed in "resource_access.<client>.roles").
+    KeycloakClientRole(String, String),
+
+    /// Validate that a certificate-bound access token (RFC 8705) is
+    /// bound to the client certificate with the specified SHA-256
+    /// thumbprint, i.e. that its "cnf"-claim contains a matching
+    /// "x5t#S256" member. See also `certificate_thumbprint`.
+    CertificateThumbprint(String),
+
+    /// Validate that a DPoP-bound access token (RFC 9449) is bound to
+    /// the key with the specified JWK thumbprint, i.e. that its
+    /// "cnf"-claim contains a matching "jkt" member. The thumbprint is
+    /// usually taken from a validated proof, see `validate_dpop_proof`.
+    DPoPBinding(String),
+
+    /// Validate that the claim with the specified name is a string
+    /// matching the specified regular expression. Note that the
+    /// expression is not anchored unless it contains `^` and `$`.
+    #[cfg(feature = "regex")]
+    ClaimMatches(String, regex::Regex),
+
+    /// Validate the claim set using a custom function, which receives
+    /// the full set of claims and returns whether they are valid. See
+    /// also `Validation::custom`.
+    ///
+    /// Custom validations run together with all other validations, and
+    /// only after the signature of the token has been verified.
+    Custom(Arc<dyn Fn(&Value) -> bool + Send + Sync>),
+
+    /// Validate that the token has not been used before, based on its
+    /// token ID ("jti"-claim) and the specified replay cache. Tokens
+    /// without an ID are rejected.
+    ///
+    /// The token ID is only recorded as used once all other
+    /// validations of the token have passed, so rejected tokens do not
+    /// prevent later uses of their ID. It is kept until the token
+    /// expires, including the leeway.
+    NoReplay(Arc<dyn ReplayCache + Send + Sync>),
+
+    /// Validate that the type of the token ("typ"-header) matches the
+    /// specified media type, e.g. `at+jwt` for access tokens as
+    /// specified in RFC 9068. As per RFC 7515, the comparison is
+    /// case-insensitive and the `application/` prefix may be omitted.
+    TokenType(String),
+
+    /// Declare support for an extension header parameter that tokens
+    /// may list in their "crit"-header, and validate its value using
+    /// the specified function. See also `Validation::critical_header`.
+    ///
+    /// As specified in RFC 7515, tokens listing any other parameter as
+    /// critical are rejected, as are tokens that omit a parameter they
+    /// list. The function is applied whenever the header is present.
+    CriticalHeader(String, Arc<dyn Fn(&Value) -> bool + Send + Sync>),
+
+    /// Allow for clock skew between the token issuer and the local
+    /// system when validating time-based claims (e.g. with
+    /// `NotExpired`). Tokens are accepted if they are valid within the
+    /// specified leeway. Defaults to no leeway.
+    Leeway(Duration),
+
+    /// Use the specified clock as the source of the current time for
+    /// time-based claim validations. Defaults to `SystemClock`.
+    TimeSource(Arc<dyn Clock + Send + Sync>),
+
+    /// Apply the specified limits on the size of the token, which is
+    /// rejected before being decoded if it exceeds them. Defaults to
+    /// `Limits::default()`.
+    Limits(Limits),
+}
+
+impl fmt::Debug for Validation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Functions, caches and clocks can not be formatted, so only
+        // their variant is printed.
+        match *self {
+            Validation::Issuer(ref a) => f.debug_tuple("Issuer").field(a).finish(),
+            Validation::OneOfIssuers(ref a) => f.debug_tuple("OneOfIssuers").field(a).finish(),
+            Validation::IssuerTemplate(ref a) => f.debug_tuple("IssuerTemplate").field(a).finish(),
+            Validation::Audience(ref a) => f.debug_tuple("Audience").field(a).finish(),
+            Validation::OneOfAudiences(ref a) => f.debug_tuple("OneOfAudiences").field(a).finish(),
+            Validation::AuthorizedParty(ref a) => f.debug_tuple("AuthorizedParty").field(a).finish(),
+            Validation::Nonce(ref a) => f.debug_tuple("Nonce").field(a).finish(),
+            Validation::AccessTokenHash(ref a) => f.debug_tuple("AccessTokenHash").field(a).finish(),
+            Validation::CodeHash(ref a) => f.debug_tuple("CodeHash").field(a).finish(),
+            Validation::OneOfAcrValues(ref a) => f.debug_tuple("OneOfAcrValues").field(a).finish(),
+            Validation::AmrContains(ref a) => f.debug_tuple("AmrContains").field(a).finish(),
+            Validation::SubjectPresent => f.write_str("SubjectPresent"),
+            Validation::ClaimsPresent(ref a) => f.debug_tuple("ClaimsPresent").field(a).finish(),
+            Validation::AnyClaimPresent(ref a) => f.debug_tuple("AnyClaimPresent").field(a).finish(),
+            Validation::ClaimAbsent(ref a) => f.debug_tuple("ClaimAbsent").field(a).finish(),
+            Validation::HasScope(ref a) => f.debug_tuple("HasScope").field(a).finish(),
+            Validation::NotExpired => f.write_str("NotExpired"),
+            Validation::NotBeforeOk => f.write_str("NotBeforeOk"),
+            Validation::IssuedAtMaxAge(ref a) => f.debug_tuple("IssuedAtMaxAge").field(a).finish(),
+            Validation::ClaimEquals(ref a, ref b) => f.debug_tuple("ClaimEquals").field(a).field(b).finish(),
+            Validation::PointerEquals(ref a, ref b) => f.debug_tuple("PointerEquals").field(a).field(b).finish(),
+            Validation::PointerContains(ref a, ref b) => f.debug_tuple("PointerContains").field(a).field(b).finish(),
+            Validation::KeycloakRealmRole(ref a) => f.debug_tuple("KeycloakRealmRole").field(a).finish(),
+            Validation::KeycloakClientRole(ref a, ref b) => f.debug_tuple("KeycloakClientRole").field(a).field(b).finish(),
+            Validation::CertificateThumbprint(ref a) => f.debug_tuple("CertificateThumbprint").field(a).finish(),
+            Validation::DPoPBinding(ref a) => f.debug_tuple("DPoPBinding").field(a).finish(),
+            #[cfg(feature = "regex")]
+            Validation::ClaimMatches(ref a, ref b) => f.debug_tuple("ClaimMatches").field(a).field(b).finish(),
+            Validation::Custom(_) => f.write_str("Custom(..)"),
+            Validation::NoReplay(_) => f.write_str("NoReplay(..)"),
+            Validation::TokenType(ref a) => f.debug_tuple("TokenType").field(a).finish(),
+            Validation::CriticalHeader(ref a, _) => f.debug_tuple("CriticalHeader").field(a).finish_non_exhaustive(),
+            Validation::Leeway(ref a) => f.debug_tuple("Leeway").field(a).finish(),
+            Validation::Limits(ref a) => f.debug_tuple("Limits").field(a).finish(),
+            Validation::TimeSource(_) => f.write_str("TimeSource(..)"),
+        }
+    }
+}
+
+impl Validation {
+    /// Construct a custom validation from a function, see
+    /// `Validation::Custom`.
+    pub fn custom<F>(validator: F) -> Validation
+    where F: Fn(&Value) -> bool + Send + Sync + 'static {
+        Validation::Custom(Arc::new(validator))
+    }
+
+    /// Construct an issuer validation, see `Validation::Issuer`.
+    pub fn issuer<S: Into<String>>(issuer: S) -> Validation {
+        Validation::Issuer(issuer.into())
+    }
+
+    /// Construct a validation accepting any of several issuers, see
+    /// `Validation::OneOfIssuers`.
+    pub fn one_of_issuers<I, S>(issuers: I) -> Validation
+    where I: IntoIterator<Item = S>, S: Into<String> {
+        Validation::OneOfIssuers(issuers.into_iter().map(Into::into).collect())
+    }
+
+    /// Construct an audience validation, see `Validation::Audience`.
+    pub fn audience<S: Into<String>>(audience: S) -> Validation {
+        Validation::Audience(audience.into())
+    }
+
+    /// Construct a validation accepting any of several audiences, see
+    /// `Validation::OneOfAudiences`.
+    pub fn one_of_audiences<I, S>(audiences: I) -> Validation
+    where I: IntoIterator<Item = S>, S: Into<String> {
+        Validation::OneOfAudiences(audiences.into_iter().map(Into::into).collect())
+    }
+
+    /// Construct a validation of the presence of several claims, see
+    /// `Validation::ClaimsPresent`.
+    pub fn claims_present<I, S>(names: I) -> Validation
+    where I: IntoIterator<Item = S>, S: Into<String> {
+        Validation::ClaimsPresent(names.into_iter().map(Into::into).collect())
+    }
+
+    /// Construct a validation of the value of a claim, see
+    /// `Validation::ClaimEquals`.
+    pub fn claim_equals<S: Into<String>, V: Into<Value>>(name: S, value: V) -> Validation {
+        Validation::ClaimEquals(name.into(), value.into())
+    }
+
+    /// Construct a scope validation, see `Validation::HasScope`.
+    pub fn has_scope<S: Into<String>>(scope: S) -> Validation {
+        Validation::HasScope(scope.into())
+    }
+
+    /// Construct a replay validation using the specified cache, see
+    /// `Validation::NoReplay`.
+    pub fn no_replay<C>(cache: C) -> Validation
+    where C: ReplayCache + Send + Sync + 'static {
+        Validation::NoReplay(Arc::new(cache))
+    }
+
+    /// Construct a validation of the "typ"-header, see
+    /// `Validation::TokenType`.
+    pub fn token_type<S: Into<String>>(typ: S) -> Validation {
+        Validation::TokenType(typ.into())
+    }
+
+    /// Construct a validation of a critical header parameter, see
+    /// `Validation::CriticalHeader`.
+    pub fn critical_header<S, F>(name: S, validator: F) -> Validation
+    where S: Into<String>, F: Fn(&Value) -> bool + Send + Sync + 'static {
+        Validation::CriticalHeader(name.into(), Arc::new(validator))
+    }
+
+    /// Use the specified clock for time-based validations, see
+    /// `Validation::TimeSource`.
+    pub fn time_source<C>(clock: C) -> Validation
+    where C: Clock + Send + Sync + 'static {
+        Validation::TimeSource(Arc::new(clock))
+    }
+}
+
+/// Description of a failed claim validation, as contained in
+/// `ValidationError::InvalidClaims`.
+///
+/// Claims are identified by their name, by their path for nested
+/// claims (e.g. `cnf.jkt`), or by the JSON pointer used for
+/// validating them. Failed validations of the `typ` header are
+/// reported as claim failures as well.
+///
+/// The most common failure causes have dedicated variants (e.g.
+/// `ClaimFailure::Expired`), which allows services to map them to
+/// different responses.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum ClaimFailure {
+    /// A required claim is missing.
+    Missing {
+        claim: String,
+    },
+
+    /// A claim does not have the expected value. If several values
+    /// are accepted, `expected` is an array of them.
+    Mismatch {
+        claim: String,
+        expected: Value,
+        found: Value,
+    },
+
+    /// The token has expired, i.e. its expiry time has passed by more
+    /// than the leeway.
+    Expired {
+        /// Time passed since the expiry time of the token.
+        expired_by: Duration,
+    },
+
+    /// The token is not valid yet, i.e. its not-before time is further
+    /// in the future than the leeway.
+    NotYetValid {
+        /// Time until the token becomes valid.
+        valid_in: Duration,
+    },
+
+    /// The token was not issued by any of the expected issuers.
+    WrongIssuer {
+        expected: Vec<String>,
+        found: String,
+    },
+
+    /// The token is not intended for any of the expected audiences.
+    WrongAudience {
+        expected: Vec<String>,
+        found: Vec<String>,
+    },
+
+    /// A claim is present, but invalid for another reason.
+    Invalid {
+        claim: String,
+        reason: &'static str,
+    },
+
+    /// A custom validation rejected the claim set.
+    Custom,
+}
+
+impl ClaimFailure {
+    /// Construct a failure for a missing claim, e.g. for reporting
+    /// failures of validations performed by the caller.
+    pub fn missing(claim: &str) -> ClaimFailure {
+        ClaimFailure::Missing { claim: claim.into() }
+    }
+
+    /// Construct a failure for a claim with an unexpected value.
+    pub fn mismatch<E: Into<Value>, F: Into<Value>>(claim: &str, expected: E, found: F) -> ClaimFailure {
+        ClaimFailure::Mismatch {
+            claim: claim.into(),
+            expected: expected.into(),
+            found: found.into(),
+        }
+    }
+
+    /// Construct a failure for an otherwise invalid claim.
+    pub fn invalid(claim: &str, reason: &'static str) -> ClaimFailure {
+        ClaimFailure::Invalid { claim: claim.into(), reason }
+    }
+
+    /// Return the name of the claim that failed validation, if the
+    /// failure relates to a single claim.
+    pub fn claim(&self) -> Option<&str> {
+        match *self {
+            ClaimFailure::Missing { ref claim } |
+            ClaimFailure::Mismatch { ref claim, .. } |
+            ClaimFailure::Invalid { ref claim, .. } => Some(claim),
+            ClaimFailure::Expired { .. } => Some("exp"),
+            ClaimFailure::NotYetValid { .. } => Some("nbf"),
+            ClaimFailure::WrongIssuer { .. } => Some("iss"),
+            ClaimFailure::WrongAudience { .. } => Some("aud"),
+            ClaimFailure::Custom => None,
+        }
+    }
+
+    /// Return the name of the failure variant, e.g. `"expired"`. Unlike
+    /// the `Display` representation, this does not contain any claim
+    /// values and can be logged safely.
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            ClaimFailure::Missing { .. } => "missing",
+            ClaimFailure::Mismatch { .. } => "mismatch",
+            ClaimFailure::Expired { .. } => "expired",
+            ClaimFailure::NotYetValid { .. } => "not_yet_valid",
+            ClaimFailure::WrongIssuer { .. } => "wrong_issuer",
+            ClaimFailure::WrongAudience { .. } => "wrong_audience",
+            ClaimFailure::Invalid { .. } => "invalid",
+            ClaimFailure::Custom => "custom",
+        }
+    }
+}
+
+impl fmt::Display for ClaimFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ClaimFailure::Missing { ref claim } => write!(f, "'{}' is missing", claim),
+            ClaimFailure::Mismatch { ref claim, ref expected, ref found } => {
+                write!(f, "'{}' is {}, expected {}", claim, found, expected)
+            },
+            ClaimFailure::Expired { expired_by } => write!(f, "token expired {}s ago", expired_by.as_secs()),
+            ClaimFailure::NotYetValid { valid_in } => {
+                write!(f, "token is not valid for another {}s", valid_in.as_secs())
+            },
+            ClaimFailure::WrongIssuer { ref expected, ref found } => {
+                write!(f, "issuer '{}' is not one of {:?}", found, expected)
+            },
+            ClaimFailure::WrongAudience { ref expected, ref found } => {
+                write!(f, "audience {:?} does not contain any of {:?}", found, expected)
+            },
+            ClaimFailure::Invalid { ref claim, reason } => write!(f, "'{}' is invalid: {}", claim, reason),
+            ClaimFailure::Custom => write!(f, "custom validation failed"),
+        }
+    }
 }
 
 /// Possible results of a token validation.
+///
+/// New error variants may be added in any release, so matches on
+/// this enumeration need a wildcard arm.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum ValidationError {
     /// Invalid number of token components (not a JWT?)
     InvalidComponents,
@@ -198,10 +1460,31 @@ pub enum ValidationError {
     /// Decoding of the provided JWK failed.
     InvalidJWK,
 
+    /// No key with the requested key ID could be found. This variant
+    /// contains the requested key ID, if any.
+    KeyNotFound(Option<String>),
+
+    /// The provided JWK does not satisfy the key policy in use. This
+    /// variant contains the size of the key in bits.
+    WeakKey(u32),
+
     /// Signature validation failed, i.e. because of a non-matching
     /// public key.
     InvalidSignature,
 
+    /// The token is signed using an algorithm that is not supported
+    /// or not allowed. This variant contains the "alg" header of the
+    /// token, if any.
+    DisallowedAlgorithm(Option<String>),
+
+    /// The token or key set exceeds the configured size limits. This
+    /// variant contains the name of the exceeded limit, see `Limits`.
+    LimitExceeded(&'static str),
+
+    /// An encrypted token could not be decrypted, e.g. because it was
+    /// encrypted for a different key or has been modified.
+    DecryptionFailed,
+
     /// An OpenSSL operation failed along the way at a point at which
     /// a more specific error variant could not be constructed.
     OpenSSL(ErrorStack),
@@ -209,13 +1492,81 @@ pub enum ValidationError {
     /// JSON decoding into a provided type failed.
     JSON(serde_json::Error),
 
-    /// One or more claim validations failed. This variant contains
-    /// human-readable validation errors.
-    InvalidClaims(Vec<&'static str>),
+    /// One or more claim validations failed. This variant contains a
+    /// description of each failed validation.
+    InvalidClaims(Vec<ClaimFailure>),
+
+    /// Reading a file (e.g. a key set file) failed.
+    IO(std::io::Error),
+
+    /// An HTTP request (e.g. for fetching a key set) failed.
+    #[cfg(any(feature = "fetch", feature = "async"))]
+    HTTP(reqwest::Error),
+
+    /// An HTTP response had a status other than `200 OK`. This
+    /// variant contains the status code of the response.
+    #[cfg(any(feature = "fetch", feature = "async"))]
+    UnexpectedStatus(u16),
+
+    /// An HTTP response did not contain a JSON document. This variant
+    /// contains the content type of the response.
+    #[cfg(any(feature = "fetch", feature = "async"))]
+    UnexpectedContentType(String),
+
+    /// An OpenID Provider Configuration document was published for a
+    /// different issuer than the one requested. This variant contains
+    /// the issuer specified in the document.
+    #[cfg(any(feature = "fetch", feature = "async"))]
+    IssuerMismatch(String),
+
+    /// A concurrent attempt to fetch a key set failed. The error
+    /// causing the failure is returned to the caller that performed
+    /// the fetch.
+    #[cfg(any(feature = "fetch", feature = "async"))]
+    RefreshFailed,
+
+    /// An operation of a cache backend failed.
+    #[cfg(any(feature = "fetch", feature = "async"))]
+    Cache(Box<dyn std::error::Error + Send + Sync>),
 }
 
 type JWTResult<T> = Result<T, ValidationError>;
 
+impl ValidationError {
+    /// Return the name of the error variant, e.g. `"invalid_signature"`.
+    /// Unlike the `Display` representation, this does not contain any
+    /// token material and can be logged or used as a metric label.
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            ValidationError::InvalidComponents => "invalid_components",
+            ValidationError::InvalidBase64(_) => "invalid_base64",
+            ValidationError::InvalidJWK => "invalid_jwk",
+            ValidationError::KeyNotFound(_) => "key_not_found",
+            ValidationError::WeakKey(_) => "weak_key",
+            ValidationError::InvalidSignature => "invalid_signature",
+            ValidationError::DisallowedAlgorithm(_) => "disallowed_algorithm",
+            ValidationError::LimitExceeded(_) => "limit_exceeded",
+            ValidationError::DecryptionFailed => "decryption_failed",
+            ValidationError::OpenSSL(_) => "openssl",
+            ValidationError::JSON(_) => "json",
+            ValidationError::InvalidClaims(_) => "invalid_claims",
+            ValidationError::IO(_) => "io",
+            #[cfg(any(feature = "fetch", feature = "async"))]
+            ValidationError::HTTP(_) => "http",
+            #[cfg(any(feature = "fetch", feature = "async"))]
+            ValidationError::UnexpectedStatus(_) => "unexpected_status",
+            #[cfg(any(feature = "fetch", feature = "async"))]
+            ValidationError::UnexpectedContentType(_) => "unexpected_content_type",
+            #[cfg(any(feature = "fetch", feature = "async"))]
+            ValidationError::IssuerMismatch(_) => "issuer_mismatch",
+            #[cfg(any(feature = "fetch", feature = "async"))]
+            ValidationError::RefreshFailed => "refresh_failed",
+            #[cfg(any(feature = "fetch", feature = "async"))]
+            ValidationError::Cache(_) => "cache",
+        }
+    }
+}
+
 impl From<ErrorStack> for ValidationError {
     fn from(err: ErrorStack) -> Self { ValidationError::OpenSSL(err) }
 }
@@ -224,10 +1575,88 @@ impl From<serde_json::Error> for ValidationError {
     fn from(err: serde_json::Error) -> Self { ValidationError::JSON(err) }
 }
 
+impl From<std::io::Error> for ValidationError {
+    fn from(err: std::io::Error) -> Self { ValidationError::IO(err) }
+}
+
 impl From<DecodeError> for ValidationError {
     fn from(err: DecodeError) -> Self { ValidationError::InvalidBase64(err) }
 }
 
+#[cfg(any(feature = "fetch", feature = "async"))]
+impl From<reqwest::Error> for ValidationError {
+    fn from(err: reqwest::Error) -> Self { ValidationError::HTTP(err) }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ValidationError::InvalidComponents => write!(f, "token does not consist of three components"),
+            ValidationError::InvalidBase64(ref err) => write!(f, "token has invalid base64-encoding: {}", err),
+            ValidationError::InvalidJWK => write!(f, "invalid JWK"),
+            ValidationError::KeyNotFound(Some(ref kid)) => write!(f, "no key with ID '{}' found", kid),
+            ValidationError::KeyNotFound(None) => write!(f, "no suitable key found"),
+            ValidationError::WeakKey(bits) => write!(f, "key of {} bits does not satisfy the key policy", bits),
+            ValidationError::InvalidSignature => write!(f, "invalid token signature"),
+            ValidationError::DisallowedAlgorithm(Some(ref alg)) => write!(f, "algorithm '{}' is not allowed", alg),
+            ValidationError::DisallowedAlgorithm(None) => write!(f, "token does not specify an algorithm"),
+            ValidationError::LimitExceeded(limit) => write!(f, "input exceeds limit '{}'", limit),
+            ValidationError::DecryptionFailed => write!(f, "decryption of token failed"),
+            ValidationError::OpenSSL(ref err) => write!(f, "OpenSSL error: {}", err),
+            ValidationError::JSON(ref err) => write!(f, "JSON error: {}", err),
+            ValidationError::InvalidClaims(ref failures) => {
+                write!(f, "claim validation failed")?;
+                for (i, failure) in failures.iter().enumerate() {
+                    write!(f, "{} {}", if i == 0 { ":" } else { ";" }, failure)?;
+                }
+                Ok(())
+            },
+            ValidationError::IO(ref err) => write!(f, "I/O error: {}", err),
+
+            #[cfg(any(feature = "fetch", feature = "async"))]
+            ValidationError::HTTP(ref err) => write!(f, "HTTP request failed: {}", err),
+
+            #[cfg(any(feature = "fetch", feature = "async"))]
+            ValidationError::UnexpectedStatus(status) => write!(f, "unexpected HTTP status {}", status),
+
+            #[cfg(any(feature = "fetch", feature = "async"))]
+            ValidationError::UnexpectedContentType(ref content_type) => {
+                write!(f, "unexpected content type '{}'", content_type)
+            },
+
+            #[cfg(any(feature = "fetch", feature = "async"))]
+            ValidationError::IssuerMismatch(ref issuer) => {
+                write!(f, "provider configuration was published for issuer '{}'", issuer)
+            },
+
+            #[cfg(any(feature = "fetch", feature = "async"))]
+            ValidationError::RefreshFailed => write!(f, "concurrent key set refresh failed"),
+
+            #[cfg(any(feature = "fetch", feature = "async"))]
+            ValidationError::Cache(ref err) => write!(f, "cache backend error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            ValidationError::InvalidBase64(ref err) => Some(err),
+            ValidationError::OpenSSL(ref err) => Some(err),
+            ValidationError::JSON(ref err) => Some(err),
+            ValidationError::IO(ref err) => Some(err),
+
+            #[cfg(any(feature = "fetch", feature = "async"))]
+            ValidationError::HTTP(ref err) => Some(err),
+
+            #[cfg(any(feature = "fetch", feature = "async"))]
+            ValidationError::Cache(ref err) => Some(err.as_ref()),
+
+            _ => None,
+        }
+    }
+}
+
 /// Attempt to extract the `kid`-claim out of a JWT's header claims.
 ///
 /// This function is normally used when a token provider has multiple
@@ -237,23 +1666,136 @@ impl From<DecodeError> for ValidationError {
 /// This is only safe if the key set containing the currently allowed
 /// key IDs is fetched from a trusted source.
 pub fn token_kid(token: &str) -> JWTResult<Option<String>> {
-    // Fetch the header component of the JWT by splitting it out and
-    // dismissing the rest.
-    let parts: Vec<&str> = token.splitn(2, '.').collect();
-    if parts.len() != 2 {
-        return Err(ValidationError::InvalidComponents);
-    }
+    with_token_kid(token, &Limits::default(), |kid| Ok(kid.map(Into::into)))
+}
+
+/// Extract the `kid`-claim of a JWT as in `token_kid` and pass it to
+/// the supplied function, without allocating a copy of it.
+pub(crate) fn with_token_kid<R, F>(token: &str, limits: &Limits, f: F) -> JWTResult<R>
+where F: FnOnce(Option<&str>) -> JWTResult<R> {
+    limits.check_token(token)?;
 
     // Decode only the first part of the token into a specialised
-    // representation:
+    // representation, which borrows the key ID from the decoded JSON
+    // unless it contains escape sequences.
+    #[derive(Deserialize)]
+    struct KidOnly<'a> {
+        #[serde(borrow)]
+        kid: Option<Cow<'a, str>>,
+    }
+
+    with_decoded_part(header_part(token)?, |json| {
+        let kid_only: KidOnly = serde_json::from_slice(json)?;
+        let result = f(kid_only.kid.as_deref());
+
+        #[cfg(feature = "tracing")]
+        if let Err(ref err) = result {
+            tracing::debug!(kid = kid_only.kid.as_deref(), error = err.kind(), "key lookup failed");
+        }
+
+        result
+    })
+}
+
+/// Extract the token from the value of an `Authorization` header
+/// using the `Bearer` scheme of [RFC
+/// 6750](https://tools.ietf.org/html/rfc6750#section-2.1). The scheme
+/// is matched case-insensitively.
+///
+/// Returns `None` for other authentication schemes and empty tokens.
+pub fn bearer_token(authorization: &str) -> Option<&str> {
+    let (scheme, token) = authorization.trim().split_once(' ')?;
+    let token = token.trim_start();
+    if scheme.eq_ignore_ascii_case("bearer") && !token.is_empty() {
+        Some(token)
+    } else {
+        None
+    }
+}
+
+/// Decode the JOSE header of a JSON Web Token, without verifying its
+/// signature.
+///
+/// Like `token_kid`, this is useful for deciding how a token should
+/// be validated (e.g. based on its type or algorithm), but none of
+/// the header members can be trusted before the token is validated.
+pub fn token_header(token: &str) -> JWTResult<JoseHeader> {
+    Limits::default().check_token(token)?;
+    deserialize_part(header_part(token)?)
+}
+
+/// Determine the signature algorithm specified in the "alg" header of
+/// a JSON Web Token, without verifying its signature.
+///
+/// This allows rejecting tokens with unexpected algorithms early and
+/// selecting a suitable key type. Returns
+/// `ValidationError::DisallowedAlgorithm` if the algorithm is missing
+/// or not supported by this library.
+pub fn token_alg(token: &str) -> JWTResult<KeyAlgorithm> {
+    token_alg_limited(token, &Limits::default())
+}
+
+/// Determine the signature algorithm of a JWT as in `token_alg`,
+/// subject to the specified limits.
+pub(crate) fn token_alg_limited(token: &str, limits: &Limits) -> JWTResult<KeyAlgorithm> {
+    limits.check_token(token)?;
+
     #[derive(Deserialize)]
-    struct KidOnly {
-        kid: Option<String>,
+    struct AlgOnly {
+        alg: Option<Value>,
+    }
+
+    let alg_only: AlgOnly = deserialize_part(header_part(token)?)?;
+    match alg_only.alg {
+        Some(ref alg) => KeyAlgorithm::deserialize(alg)
+            .map_err(|_| ValidationError::DisallowedAlgorithm(alg.as_str().map(Into::into))),
+        None => Err(ValidationError::DisallowedAlgorithm(None)),
     }
+}
+
+/// Decode the header and claims of a JSON Web Token **without**
+/// verifying its signature or applying any validations.
+///
+/// This is meant for inspecting tokens before they can be validated,
+/// e.g. for selecting the key store of a tenant based on the "iss"
+/// claim. Nothing in the decoded token can be trusted, so it must
+/// never be used for any other decisions. Tokens must always be
+/// validated afterwards.
+pub fn dangerous_decode_unverified(token: &str) -> JWTResult<UnverifiedJWT> {
+    Limits::default().check_token(token)?;
+    let [headers, claims, _] = token_parts(token)?;
+
+    Ok(UnverifiedJWT {
+        headers: deserialize_part(headers)?,
+        claims: deserialize_part(claims)?,
+    })
+}
+
+/// Compute the hash of an access token or authorization code as used
+/// in the "at_hash" and "c_hash" claims of OpenID Connect ID tokens.
+///
+/// The hash is the base64url-encoded left half of the digest of the
+/// value, using the hash function of the signature algorithm of the ID
+/// token (e.g. SHA-256 for `RS256`). See [OpenID Connect Core, section
+/// 3.1.3.6](https://openid.net/specs/openid-connect-core-1_0.html#CodeIDToken).
+///
+/// Returns `None` if the algorithm is not known.
+pub fn oidc_token_hash(value: &str, alg: &str) -> Option<String> {
+    let digest = match alg.get(alg.len().saturating_sub(3)..) {
+        Some("256") => openssl::sha::sha256(value.as_bytes()).to_vec(),
+        Some("384") => openssl::sha::sha384(value.as_bytes()).to_vec(),
+        Some("512") => openssl::sha::sha512(value.as_bytes()).to_vec(),
+        _ => return None,
+    };
 
-    let kid_only: KidOnly = deserialize_part(parts[0])?;
+    Some(base64::encode_config(&digest[..digest.len() / 2], base64::URL_SAFE_NO_PAD))
+}
 
-    Ok(kid_only.kid)
+/// Compute the base64url-encoded SHA-256 thumbprint of a DER-encoded
+/// X.509 certificate, as used in the "x5t#S256" member of the
+/// "cnf"-claim of certificate-bound access tokens (RFC 8705).
+pub fn certificate_thumbprint(der: &[u8]) -> String {
+    base64::encode_config(&openssl::sha::sha256(der), base64::URL_SAFE_NO_PAD)
 }
 
 /// Validate the signature of a JSON Web Token and optionally apply
@@ -264,33 +1806,130 @@ pub fn token_kid(token: &str) -> JWTResult<Option<String>> {
 /// If validation succeeds a representation of the token is returned
 /// that contains the header and claims as simple JSON values.
 ///
+/// The token can be passed as any string type, e.g. `&str`, `String`
+/// or `Arc<str>`.
+///
 /// It is the user's task to ensure that the correct JWK is passed in
 /// for validation.
-pub fn validate(token: &str,
-                jwk: &JWK,
-                validations: Vec<Validation>) -> JWTResult<ValidJWT> {
+pub fn validate<T: AsRef<str>>(token: T,
+                               jwk: &JWK,
+                               validations: Vec<Validation>) -> JWTResult<ValidJWT> {
+    validate_against(token.as_ref(), Some(jwk), &validations)
+}
+
+/// Validate a JSON Web Token as in `validate` and deserialize its
+/// claims into the specified type.
+///
+/// ```rust
+/// # use alcoholic_jwt::{validate_into, JWK, Validation, ValidationError};
+/// #[derive(serde_derive::Deserialize)]
+/// struct Claims {
+///     sub: String,
+///     email: Option<String>,
+/// }
+///
+/// fn subject(token: &str, jwk: &JWK) -> Result<String, ValidationError> {
+///     let claims: Claims = validate_into(token, jwk, vec![Validation::SubjectPresent])?;
+///     Ok(claims.sub)
+/// }
+/// ```
+pub fn validate_into<C, T>(token: T,
+                           jwk: &JWK,
+                           validations: Vec<Validation>) -> JWTResult<C>
+where C: DeserializeOwned, T: AsRef<str> {
+    let valid_jwt = validate(token, jwk, validations)?;
+    serde_json::from_value(valid_jwt.claims).map_err(Into::into)
+}
+
+/// Validate a JSON Web Token against a set of candidate keys, for
+/// example all keys returned by `JWKS::find_all`.
+///
+/// The signature is checked against each candidate in turn and claim
+/// validations are applied once a matching key has been found. This
+/// is useful during key rotation windows in which a provider may
+/// publish several keys with the same key ID.
+///
+/// If no key can verify the signature, the error encountered for the
+/// last candidate is returned.
+pub fn validate_any<'a, I, T>(token: T,
+                              jwks: I,
+                              validations: Vec<Validation>) -> JWTResult<ValidJWT>
+where I: IntoIterator<Item = &'a JWK>, T: AsRef<str> {
+    validate_against(token.as_ref(), jwks, &validations)
+}
+
+/// Validate a JSON Web Token against a set of candidate keys and
+/// apply a set of borrowed claim validations, see `validate_any`.
+pub(crate) fn validate_against<'a, I>(token: &str,
+                                      jwks: I,
+                                      validations: &[Validation]) -> JWTResult<ValidJWT>
+where I: IntoIterator<Item = &'a JWK> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("validate_token").entered();
+
+    let valid_jwt = verify_against(token, jwks, &token_limits(validations))?;
+    validate_claim_set(&valid_jwt.headers, &valid_jwt.claims, validations)?;
+    Ok(valid_jwt)
+}
+
+/// Verify the signature of a token using any of the specified keys
+/// and decode its components, without applying claim validations.
+pub(crate) fn verify_against<'a, I>(token: &str, jwks: I, limits: &Limits) -> JWTResult<ValidJWT>
+where I: IntoIterator<Item = &'a JWK> {
+    limits.check_token(token)?;
+
     let jwt = JWT(token);
-    let public_key = public_key_from_jwk(&jwk)?;
-    validate_jwt_signature(&jwt, public_key)?;
+    let mut result = Err(ValidationError::InvalidSignature);
 
-    // Split out all three parts of the JWT this time, deserialising
-    // the first and second as appropriate.
-    let parts: Vec<&str> = jwt.0.splitn(3, '.').collect();
-    if parts.len() != 3 {
-        // This is unlikely considering that validation has already
-        // been performed at this point, but better safe than sorry.
-        return Err(ValidationError::InvalidComponents)
+    for jwk in jwks {
+        result = jwk.public_key()
+            .and_then(|public_key| verify_signature(&jwt, public_key));
+
+        if result.is_ok() {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(kid = jwk.kid.as_deref(), "signature verified");
+            break;
+        }
     }
 
-    // Perform claim validations before constructing the valid token:
-    let partial_claims = deserialize_part(parts[1])?;
-    validate_claims(partial_claims, validations)?;
+    #[cfg(feature = "tracing")]
+    if let Err(ref err) = result {
+        tracing::debug!(error = err.kind(), "signature verification failed");
+    }
 
-    let headers = deserialize_part(parts[0])?;
-    let claims = deserialize_part(parts[1])?;
-    let valid_jwt = ValidJWT { headers, claims };
+    result?;
+    decode_token(&jwt)
+}
 
-    Ok(valid_jwt)
+/// Validate a JSON Web Token using a key from the specified key
+/// provider.
+///
+/// The key is selected based on the `kid` header of the token, see
+/// `KeyProvider::key_for`. Signatures and claims are validated as in
+/// `validate`.
+pub fn validate_with<P, T>(token: T,
+                           provider: &P,
+                           validations: Vec<Validation>) -> JWTResult<ValidJWT>
+where P: KeyProvider + ?Sized, T: AsRef<str> {
+    let token = token.as_ref();
+    let jwk = with_token_kid(token, &token_limits(&validations), |kid| provider.key_for(kid))?;
+    validate_against(token, Some(jwk.as_ref()), &validations)
+}
+
+/// Asynchronous equivalent of `validate_with`, which retrieves the key
+/// using `KeyProvider::key_for_async`.
+///
+/// With a `KeyStore` this fetches or refreshes the key set without
+/// blocking if required, which makes this the entry point of choice
+/// for asynchronous services.
+pub async fn validate_async<P, T>(token: T,
+                                  provider: &P,
+                                  validations: Vec<Validation>) -> JWTResult<ValidJWT>
+where P: KeyProvider + ?Sized, T: AsRef<str> {
+    let token = token.as_ref();
+    let kid = with_token_kid(token, &token_limits(&validations), |kid| Ok(kid.map(String::from)))?;
+    let jwk = provider.key_for_async(kid.as_deref()).await?;
+    validate_against(token, Some(jwk.as_ref()), &validations)
 }
 
 // Internal implementation
@@ -298,6 +1937,21 @@ pub fn validate(token: &str,
 // The functions in the following section are not part of the public
 // API of this library.
 
+/// Decode the components of a JWT whose signature has already been
+/// verified.
+fn decode_token(jwt: &JWT) -> JWTResult<ValidJWT> {
+    // Split out all three parts of the JWT this time, deserialising
+    // the first and second as appropriate. Failing to split is
+    // unlikely considering that validation has already been performed
+    // at this point, but better safe than sorry.
+    let [headers, claims, _] = token_parts(jwt.0)?;
+
+    let headers = deserialize_part(headers)?;
+    let claims = deserialize_part(claims)?;
+
+    Ok(ValidJWT { headers, claims })
+}
+
 /// Decode a single key fragment (base64-url encoded integer) to an
 /// OpenSSL BigNum.
 fn decode_fragment(fragment: &str) -> JWTResult<BigNum> {
@@ -315,147 +1969,639 @@ fn public_key_from_jwk(jwk: &JWK) -> JWTResult<Rsa<Public>> {
     Rsa::from_public_components(jwk_n, jwk_e).map_err(Into::into)
 }
 
+/// Determine the limits applied to tokens from a set of validations.
+/// If several limits are specified, the last one wins.
+pub(crate) fn token_limits(validations: &[Validation]) -> Limits {
+    validations.iter()
+        .rev()
+        .find_map(|v| match *v {
+            Validation::Limits(limits) => Some(limits),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+/// Return the (encoded) header component of a JWT.
+fn header_part(token: &str) -> JWTResult<&str> {
+    token.split_once('.')
+        .map(|(header, _)| header)
+        .ok_or(ValidationError::InvalidComponents)
+}
+
+/// Split a JWT into its three (encoded) components.
+fn token_parts(token: &str) -> JWTResult<[&str; 3]> {
+    let mut parts = token.splitn(3, '.');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(headers), Some(claims), Some(signature)) => Ok([headers, claims, signature]),
+        _ => Err(ValidationError::InvalidComponents),
+    }
+}
+
+thread_local! {
+    /// Buffer reused for decoding token components, which avoids an
+    /// allocation for every decoded component.
+    static DECODE_BUFFER: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Decode a base64-URL encoded string and pass the resulting bytes to
+/// the supplied function.
+fn with_decoded_part<R, F>(part: &str, f: F) -> JWTResult<R>
+where F: FnOnce(&[u8]) -> JWTResult<R> {
+    DECODE_BUFFER.with(|buffer| {
+        // The buffer is in use if the function decodes another part,
+        // in which case a temporary buffer is used instead.
+        let mut temporary = Vec::new();
+        let mut borrowed = buffer.try_borrow_mut();
+        let decoded = match borrowed {
+            Ok(ref mut buffer) => &mut **buffer,
+            Err(_) => &mut temporary,
+        };
+
+        decoded.clear();
+        base64::decode_config_buf(part, jwt_forgiving(), decoded)?;
+        f(decoded)
+    })
+}
+
 /// Decode a base64-URL encoded string and deserialise the resulting
 /// JSON.
 fn deserialize_part<T: DeserializeOwned>(part: &str) -> JWTResult<T> {
-    let json = base64::decode_config(part, jwt_forgiving())?;
-    serde_json::from_slice(&json).map_err(Into::into)
+    with_decoded_part(part, |json| serde_json::from_slice(json).map_err(Into::into))
 }
 
 /// Validate the signature on a JWT using a provided public key.
 ///
 /// A JWT is made up of three components (headers, claims, signature)
 /// - only the first two are part of the signed data.
+#[cfg(test)]
 fn validate_jwt_signature(jwt: &JWT, key: Rsa<Public>) -> JWTResult<()> {
-    let key = PKey::from_rsa(key)?;
-    let mut verifier = Verifier::new(MessageDigest::sha256(), &key)?;
+    verify_signature(jwt, &PKey::from_rsa(key)?)
+}
 
-    // Split the token from the back to a maximum of two elements.
-    // There are technically three components using the same separator
-    // ('.'), but we are interested in the first two together and
+/// Validate the signature on a JWT using an already constructed
+/// OpenSSL key, see `validate_jwt_signature`.
+fn verify_signature(jwt: &JWT, key: &PKey<Public>) -> JWTResult<()> {
+    // Split the token at its last separator. There are technically
+    // three components using the same separator ('.'), but we are
+    // interested in the first two together (the signed payload) and
     // splitting them is unnecessary.
-    let token_parts: Vec<&str> = jwt.0.rsplitn(2, '.').collect();
-    if token_parts.len() != 2 {
-        return Err(ValidationError::InvalidComponents);
-    }
+    let (data, sig_b64) = jwt.0.rsplit_once('.')
+        .ok_or(ValidationError::InvalidComponents)?;
 
-    // Second element of the vector will be the signed payload.
-    let data = token_parts[1];
+    verify_signed_data(&[data.as_bytes()], sig_b64, key)
+}
 
-    // First element of the vector will be the (encoded) signature.
-    let sig_b64 = token_parts[0];
-    let sig = base64::decode_config(sig_b64, jwt_forgiving())?;
+/// Verify a signature over data supplied in several pieces, which are
+/// concatenated to form the signing input.
+fn verify_signed_data(data: &[&[u8]], sig_b64: &str, key: &PKey<Public>) -> JWTResult<()> {
+    let mut verifier = Verifier::new(MessageDigest::sha256(), key)?;
 
     // Verify signature by inserting the payload data and checking it
     // against the decoded signature.
-    verifier.update(data.as_bytes())?;
+    for piece in data {
+        verifier.update(piece)?;
+    }
 
-    match verifier.verify(&sig)? {
+    with_decoded_part(sig_b64, |sig| match verifier.verify(sig)? {
         true  => Ok(()),
         false => Err(ValidationError::InvalidSignature),
-    }
+    })
 }
 
-/// Internal helper enum for PartialClaims that supports single or
-/// multiple audiences
-#[derive(Deserialize)]
-#[serde(untagged)]
-enum Audience {
-    Single(String),
-    Multi(Vec<String>)
+/// Internal helper struct for the parameters of time-based claim
+/// validations, which are shared by all validations of a token.
+struct TimeContext {
+    /// Current time as a duration since the UNIX epoch.
+    now: Duration,
+
+    /// Allowed clock skew.
+    leeway: Duration,
 }
 
-/// Internal helper struct for claims that are relevant for claim
-/// validations.
-#[derive(Deserialize)]
-struct PartialClaims {
-    aud: Option<Audience>,
-    iss: Option<String>,
-    sub: Option<String>,
-    exp: Option<u64>,
+impl TimeContext {
+    fn new(validations: &[Validation]) -> TimeContext {
+        // Determine the current timestamp in seconds since the UNIX
+        // epoch, using the last specified time source if any.
+        let clock = validations.iter()
+            .rev()
+            .find_map(|v| match *v {
+                Validation::TimeSource(ref clock) => Some(clock.now()),
+                _ => None,
+            });
+
+        let now = clock.unwrap_or_else(SystemTime::now)
+            .duration_since(UNIX_EPOCH)
+            // this is an unrecoverable, critical error. There aren't
+            // many ways this can occur, other than system time being
+            // set into the far future or this library being used in
+            // some sort of future museum.
+            .expect("system time is likely incorrect");
+
+        // If several leeways are specified, the last one wins.
+        let leeway = validations.iter()
+            .rev()
+            .find_map(|v| match *v {
+                Validation::Leeway(leeway) => Some(leeway),
+                _ => None,
+            })
+            .unwrap_or_default();
+
+        TimeContext { now, leeway }
+    }
 }
 
 /// Apply a single validation to the claim set of a token.
-fn apply_validation(claims: &PartialClaims,
-                    validation: Validation) -> Result<(), &'static str> {
-    match validation {
+fn apply_validation(claims: &RegisteredClaims,
+                    all_claims: &Value,
+                    headers: &Value,
+                    time: &TimeContext,
+                    validation: &Validation) -> Result<(), ClaimFailure> {
+    // The value of a claim as found in the token, for reporting
+    // mismatches.
+    let found = |name: &str| all_claims.get(name).cloned().unwrap_or(Value::Null);
+
+    match *validation {
         // Validate that an 'iss' claim is present and matches the
         // supplied value.
-        Validation::Issuer(iss) => {
+        Validation::Issuer(ref iss) => {
+            match claims.iss {
+                None => Err(ClaimFailure::missing("iss")),
+                Some(ref claim) => if *claim == *iss {
+                    Ok(())
+                } else {
+                    Err(ClaimFailure::WrongIssuer { expected: vec![iss.clone()], found: claim.clone() })
+                }
+            }
+        },
+
+        // Validate that an 'iss' claim is present and matches any of
+        // the supplied values.
+        Validation::OneOfIssuers(ref issuers) => {
             match claims.iss {
-                None => Err("'iss' claim is missing"),
-                Some(ref claim) => if *claim == iss {
+                None => Err(ClaimFailure::missing("iss")),
+                Some(ref claim) => if issuers.contains(claim) {
                     Ok(())
                 } else {
-                    Err("'iss' claim does not match")
+                    Err(ClaimFailure::WrongIssuer { expected: issuers.clone(), found: claim.clone() })
                 }
             }
         },
 
+        Validation::IssuerTemplate(ref template) => {
+            let expected = expand_template(template, all_claims);
+            match claims.iss {
+                None => Err(ClaimFailure::missing("iss")),
+                Some(ref claim) if Some(claim) == expected.as_ref() => Ok(()),
+                Some(ref claim) => Err(ClaimFailure::WrongIssuer {
+                    expected: vec![expected.unwrap_or_else(|| template.clone())],
+                    found: claim.clone(),
+                }),
+            }
+        },
+
         // Validate that an 'aud' claim is present and matches the
         // supplied value.
-        Validation::Audience(aud) => {
+        Validation::Audience(ref aud) => {
             match claims.aud {
-                None => Err("'aud' claim is missing"),
-                Some(Audience::Single(ref claim)) => if *claim == aud {
+                None => Err(ClaimFailure::missing("aud")),
+                Some(ref claim) => if claim.contains(aud) {
                     Ok(())
                 } else {
-                    Err("'aud' claim does not match")
-                },
-                Some(Audience::Multi(ref claims)) => if claims.contains(&aud) {
+                    Err(ClaimFailure::WrongAudience { expected: vec![aud.clone()], found: claim.values() })
+                }
+            }
+        },
+
+        // Validate that an 'aud' claim is present and matches any of
+        // the supplied values.
+        Validation::OneOfAudiences(ref auds) => {
+            match claims.aud {
+                None => Err(ClaimFailure::missing("aud")),
+                Some(ref claim) => if auds.iter().any(|aud| claim.contains(aud)) {
                     Ok(())
                 } else {
-                    Err("'aud' claim does not match")
+                    Err(ClaimFailure::WrongAudience { expected: auds.clone(), found: claim.values() })
                 }
             }
         },
 
+        // Validate that an 'azp' claim, if present, matches the
+        // supplied client ID.
+        Validation::AuthorizedParty(ref azp) => match all_claims.get("azp") {
+            None => Ok(()),
+            Some(claim) => if claim.as_str() == Some(azp.as_str()) {
+                Ok(())
+            } else {
+                Err(ClaimFailure::mismatch("azp", azp.as_str(), claim.clone()))
+            }
+        },
+
+        // The nonce is compared in constant time, as it is a secret
+        // known only to the client. For the same reason, it is not
+        // included in the failure.
+        Validation::Nonce(ref nonce) => match all_claims.get("nonce").and_then(Value::as_str) {
+            None => Err(ClaimFailure::missing("nonce")),
+            Some(claim) => if claim.len() == nonce.len()
+                && openssl::memcmp::eq(claim.as_bytes(), nonce.as_bytes()) {
+                Ok(())
+            } else {
+                Err(ClaimFailure::invalid("nonce", "does not match the nonce of the request"))
+            }
+        },
+
+        Validation::AccessTokenHash(ref access_token) => {
+            check_token_hash(headers, all_claims, "at_hash", access_token)
+        },
+
+        Validation::CodeHash(ref code) => {
+            check_token_hash(headers, all_claims, "c_hash", code)
+        },
+
+        Validation::OneOfAcrValues(ref values) => match all_claims.get("acr").and_then(Value::as_str) {
+            None => Err(ClaimFailure::missing("acr")),
+            Some(acr) => if values.iter().any(|value| value == acr) {
+                Ok(())
+            } else {
+                Err(ClaimFailure::mismatch("acr", values.as_slice(), found("acr")))
+            }
+        },
+
+        Validation::AmrContains(ref method) => match all_claims.get("amr").and_then(Value::as_array) {
+            None => Err(ClaimFailure::missing("amr")),
+            Some(amr) => if amr.iter().any(|value| value.as_str() == Some(method.as_str())) {
+                Ok(())
+            } else {
+                Err(ClaimFailure::mismatch("amr", method.as_str(), found("amr")))
+            }
+        },
+
         Validation::SubjectPresent => match claims.sub {
             Some(_) => Ok(()),
-            None => Err("'sub' claim is missing"),
+            None => Err(ClaimFailure::missing("sub")),
+        },
+
+        // Only the first missing claim is reported.
+        Validation::ClaimsPresent(ref names) => {
+            let missing = names.iter().find(|name| match all_claims.get(name.as_str()) {
+                None | Some(Value::Null) => true,
+                Some(_) => false,
+            });
+
+            match missing {
+                None => Ok(()),
+                Some(name) => Err(ClaimFailure::missing(name)),
+            }
+        },
+
+        Validation::AnyClaimPresent(ref names) => {
+            let present = names.iter().any(|name| match all_claims.get(name.as_str()) {
+                None | Some(Value::Null) => false,
+                Some(_) => true,
+            });
+
+            match names.first() {
+                Some(name) if !present => Err(ClaimFailure::missing(name)),
+                _ => Ok(()),
+            }
+        },
+
+        Validation::ClaimAbsent(ref name) => match all_claims.get(name.as_str()) {
+            None => Ok(()),
+            Some(_) => Err(ClaimFailure::invalid(name, "must not be present")),
+        },
+
+        Validation::HasScope(ref scope) => {
+            let scopes: Vec<_> = token_scopes(all_claims).collect();
+            if scopes.contains(&scope.as_str()) {
+                Ok(())
+            } else if scopes.is_empty() {
+                Err(ClaimFailure::missing("scope"))
+            } else {
+                Err(ClaimFailure::mismatch("scope", scope.as_str(), scopes))
+            }
         },
 
         Validation::NotExpired => match claims.exp {
-            None => Err("'exp' claim is missing"),
+            None => Err(ClaimFailure::missing("exp")),
             Some(exp) => {
-                // Determine the current timestamp in seconds since
-                // the UNIX epoch.
-                let now = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    // this is an unrecoverable, critical error. There
-                    // aren't many ways this can occur, other than
-                    // system time being set into the far future or
-                    // this library being used in some sort of future
-                    // museum.
-                    .expect("system time is likely incorrect");
-
                 // Convert the expiry time (which is also in epoch
                 // seconds) to a duration.
                 let exp_duration = Duration::from_secs(exp);
 
-                // The token has not expired if the expiry duration is
-                // larger than (i.e. in the future from) the current
-                // time.
-                if exp_duration > now {
+                // The token has not expired if the expiry duration
+                // plus the leeway is larger than (i.e. in the future
+                // from) the current time.
+                if exp_duration.saturating_add(time.leeway) > time.now {
+                    Ok(())
+                } else {
+                    Err(ClaimFailure::Expired { expired_by: time.now - exp_duration })
+                }
+            }
+        },
+
+        Validation::NotBeforeOk => match claims.nbf {
+            None => Ok(()),
+
+            // The token may be used if the not-before time is not
+            // further in the future than the leeway.
+            Some(nbf) => if Duration::from_secs(nbf) <= time.now.saturating_add(time.leeway) {
+                Ok(())
+            } else {
+                Err(ClaimFailure::NotYetValid { valid_in: Duration::from_secs(nbf) - time.now })
+            }
+        },
+
+        Validation::IssuedAtMaxAge(ref max_age) => match claims.iat {
+            None => Err(ClaimFailure::missing("iat")),
+            Some(iat) => if Duration::from_secs(iat).saturating_add(*max_age).saturating_add(time.leeway) > time.now {
+                Ok(())
+            } else {
+                Err(ClaimFailure::invalid("iat", "token is too old"))
+            }
+        },
+
+        Validation::ClaimEquals(ref name, ref expected) => match all_claims.get(name) {
+            None => Err(ClaimFailure::missing(name)),
+            Some(value) => if *value == *expected {
+                Ok(())
+            } else {
+                Err(ClaimFailure::mismatch(name, expected.clone(), value.clone()))
+            }
+        },
+
+        Validation::PointerEquals(ref pointer, ref expected) => match all_claims.pointer(pointer) {
+            None => Err(ClaimFailure::missing(pointer)),
+            Some(value) => if *value == *expected {
+                Ok(())
+            } else {
+                Err(ClaimFailure::mismatch(pointer, expected.clone(), value.clone()))
+            }
+        },
+
+        Validation::PointerContains(ref pointer, ref expected) => match all_claims.pointer(pointer) {
+            None => Err(ClaimFailure::missing(pointer)),
+            Some(Value::Array(values)) if values.contains(expected) => Ok(()),
+            Some(value) => Err(ClaimFailure::mismatch(pointer, expected.clone(), value.clone())),
+        },
+
+        Validation::KeycloakRealmRole(ref role) => {
+            has_role(all_claims.get("realm_access"), "realm_access.roles", role)
+        },
+
+        Validation::KeycloakClientRole(ref client, ref role) => {
+            let access = all_claims.get("resource_access").and_then(|access| access.get(client));
+            has_role(access, &format!("resource_access.{}.roles", client), role)
+        },
+
+        Validation::CertificateThumbprint(ref thumbprint) => {
+            let cnf = all_claims.get("cnf").and_then(|cnf| cnf.get("x5t#S256"));
+            match cnf {
+                None => Err(ClaimFailure::missing("cnf.x5t#S256")),
+                Some(claim) => if claim.as_str() == Some(thumbprint.as_str()) {
+                    Ok(())
+                } else {
+                    Err(ClaimFailure::mismatch("cnf.x5t#S256", thumbprint.as_str(), claim.clone()))
+                }
+            }
+        },
+
+        Validation::DPoPBinding(ref jkt) => {
+            match all_claims.get("cnf").and_then(|cnf| cnf.get("jkt")) {
+                None => Err(ClaimFailure::missing("cnf.jkt")),
+                Some(claim) => if claim.as_str() == Some(jkt.as_str()) {
                     Ok(())
                 } else {
-                    Err("token has expired")
+                    Err(ClaimFailure::mismatch("cnf.jkt", jkt.as_str(), claim.clone()))
+                }
+            }
+        },
+
+        #[cfg(feature = "regex")]
+        Validation::ClaimMatches(ref name, ref pattern) => match all_claims.get(name) {
+            None => Err(ClaimFailure::missing(name)),
+            Some(Value::String(value)) if pattern.is_match(value) => Ok(()),
+            Some(value) => Err(ClaimFailure::mismatch(name, pattern.as_str(), value.clone())),
+        },
+
+        Validation::NoReplay(ref cache) => match claims.jti.as_deref() {
+            None => Err(ClaimFailure::missing("jti")),
+            Some(jti) => {
+                // Expired tokens are accepted within the leeway, so
+                // their IDs must be kept for at least as long.
+                let exp = claims.exp
+                    .map(|exp| Duration::from_secs(exp).saturating_add(time.leeway))
+                    .and_then(|exp| UNIX_EPOCH.checked_add(exp));
+                if cache.seen_at(jti, exp, UNIX_EPOCH + time.now) {
+                    Err(ClaimFailure::invalid("jti", "token has already been used"))
+                } else {
+                    Ok(())
                 }
             }
         },
+
+        Validation::CriticalHeader(ref name, ref validator) => match headers.get(name.as_str()) {
+            Some(header) if !validator(header) => Err(ClaimFailure::invalid(name, "rejected by validation")),
+            _ => Ok(()),
+        },
+
+        Validation::TokenType(ref typ) => match headers.get("typ") {
+            None => Err(ClaimFailure::missing("typ")),
+            Some(header) => if header.as_str().is_some_and(|header| media_type_eq(header, typ)) {
+                Ok(())
+            } else {
+                Err(ClaimFailure::mismatch("typ", typ.as_str(), header.clone()))
+            }
+        },
+
+        Validation::Custom(ref validator) => if validator(all_claims) {
+            Ok(())
+        } else {
+            Err(ClaimFailure::Custom)
+        },
+
+        // The leeway and time source are applied by the other
+        // validations
+        Validation::Leeway(_) | Validation::TimeSource(_) | Validation::Limits(_) => Ok(()),
+    }
+}
+
+/// Check a hash claim of an ID token ("at_hash" or "c_hash") against
+/// the value it was computed from, using the algorithm specified in
+/// the token header.
+fn check_token_hash(headers: &Value, claims: &Value, name: &str, value: &str) -> Result<(), ClaimFailure> {
+    let claim = claims.get(name).and_then(Value::as_str)
+        .ok_or_else(|| ClaimFailure::missing(name))?;
+
+    let expected = headers.get("alg")
+        .and_then(Value::as_str)
+        .and_then(|alg| oidc_token_hash(value, alg));
+
+    match expected {
+        Some(ref expected) if claim == expected => Ok(()),
+        Some(_) => Err(ClaimFailure::invalid(name, "does not match the hashed value")),
+        None => Err(ClaimFailure::invalid(name, "can not be computed for the token algorithm")),
+    }
+}
+
+/// Replace the `{claim}` placeholders in a template with the values
+/// of the respective claims. Returns `None` if a placeholder refers to
+/// a claim that is missing or not a string.
+fn expand_template(template: &str, claims: &Value) -> Option<String> {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        let end = start + rest[start..].find('}')?;
+        result.push_str(&rest[..start]);
+        result.push_str(claims.get(&rest[start + 1..end])?.as_str()?);
+        rest = &rest[end + 1..];
+    }
+
+    result.push_str(rest);
+    Some(result)
+}
+
+/// Check whether a Keycloak access object (containing a "roles"
+/// array) grants the specified role.
+fn has_role(access: Option<&Value>, claim: &str, role: &str) -> Result<(), ClaimFailure> {
+    match access.and_then(|access| access.get("roles")) {
+        None => Err(ClaimFailure::missing(claim)),
+        Some(Value::Array(roles)) if roles.iter().any(|r| r.as_str() == Some(role)) => Ok(()),
+        Some(roles) => Err(ClaimFailure::mismatch(claim, role, roles.clone())),
     }
 }
 
-/// Apply all requested validations to a partial claim set.
-fn validate_claims(claims: PartialClaims,
+/// Compare two media types as used in JOSE headers, which may omit
+/// the `application/` prefix.
+fn media_type_eq(left: &str, right: &str) -> bool {
+    let strip = |media_type: &str| -> String {
+        let media_type = media_type.to_ascii_lowercase();
+        match media_type.strip_prefix("application/") {
+            Some(subtype) => subtype.into(),
+            None => media_type,
+        }
+    };
+
+    strip(left) == strip(right)
+}
+
+/// Collect the scopes granted by a token from its "scope" and "scp"
+/// claims, each of which may be a space-delimited string or an array
+/// of strings.
+fn token_scopes(claims: &Value) -> impl Iterator<Item = &str> {
+    ["scope", "scp"].iter()
+        .filter_map(move |name| claims.get(name))
+        .flat_map(|value| -> Box<dyn Iterator<Item = &str>> {
+            match value {
+                Value::String(scopes) => Box::new(scopes.split_whitespace()),
+                Value::Array(scopes) => Box::new(scopes.iter().filter_map(Value::as_str)),
+                _ => Box::new(std::iter::empty()),
+            }
+        })
+}
+
+/// Apply all requested validations to the header and claim set of a
+/// token. This is a convenience wrapper around `validate_claim_set`
+/// for the tests.
+#[cfg(test)]
+fn validate_claims(headers: &Value,
+                   claims: &Value,
                    validations: Vec<Validation>) -> JWTResult<()> {
-    let validation_errors: Vec<_> = validations.into_iter()
-        .map(|v| apply_validation(&claims, v))
-        .filter_map(|result| match result {
-            Ok(_)    => None,
-            Err(err) => Some(err),
+    validate_claim_set(headers, claims, &validations)
+}
+
+/// Header parameters defined by RFC 7515, which must not be listed in
+/// the "crit"-header.
+const REGISTERED_HEADERS: &[&str] = &[
+    "alg", "jku", "jwk", "kid", "x5u", "x5c", "x5t", "x5t#S256", "typ", "cty", "crit",
+];
+
+/// Check the "crit"-header of a token against the critical header
+/// parameters declared using `Validation::CriticalHeader`, as
+/// specified in RFC 7515, section 4.1.11.
+fn check_critical(headers: &Value, validations: &[Validation]) -> Vec<ClaimFailure> {
+    let crit = match headers.get("crit") {
+        None => return vec![],
+        Some(crit) => crit,
+    };
+
+    let names: Option<Vec<&str>> = crit.as_array()
+        .filter(|names| !names.is_empty())
+        .and_then(|names| names.iter().map(Value::as_str).collect());
+
+    let names = match names {
+        Some(names) => names,
+        None => return vec![ClaimFailure::invalid("crit", "must be a non-empty list of header names")],
+    };
+
+    let understood = |name: &str| validations.iter().any(|v| match *v {
+        Validation::CriticalHeader(ref header, _) => header == name,
+        _ => false,
+    });
+
+    names.into_iter()
+        .filter_map(|name| if REGISTERED_HEADERS.contains(&name) {
+            Some(ClaimFailure::invalid(name, "registered header can not be critical"))
+        } else if !understood(name) {
+            Some(ClaimFailure::invalid(name, "critical header is not supported"))
+        } else if headers.get(name).is_none() {
+            Some(ClaimFailure::missing(name))
+        } else {
+            None
         })
-        .collect();
+        .collect()
+}
+
+/// Apply all requested validations to the header and claim set of a
+/// token.
+fn validate_claim_set(headers: &Value,
+                      claims: &Value,
+                      validations: &[Validation]) -> JWTResult<()> {
+    validate_shared_claim_set(std::iter::once(headers), claims, validations)
+}
+
+/// Apply all requested validations to a claim set shared by several
+/// tokens, such as the signatures of a JWS in JSON serialisation.
+///
+/// Validations are applied together with each of the headers, and
+/// the errors for the first header that fails them are returned.
+/// Replay detection only records the claim set once.
+fn validate_shared_claim_set<'a, H>(headers: H,
+                                    claims: &Value,
+                                    validations: &[Validation]) -> JWTResult<()>
+where H: IntoIterator<Item = &'a Value> {
+    let registered = RegisteredClaims::deserialize(claims)?;
+    let time = TimeContext::new(validations);
+    let is_replay = |v: &&Validation| matches!(v, Validation::NoReplay(_));
+    let mut validation_errors = vec![];
+    let mut first = None;
+
+    for headers in headers {
+        first.get_or_insert(headers);
+        validation_errors = check_critical(headers, validations);
+        validation_errors.extend(validations.iter()
+            .filter(|v| !is_replay(v))
+            .map(|v| apply_validation(&registered, claims, headers, &time, v))
+            .filter_map(Result::err));
+
+        if !validation_errors.is_empty() {
+            break;
+        }
+    }
+
+    // Replay detection records the use of the token, which must only
+    // happen once the token has passed all other validations.
+    if let (true, Some(headers)) = (validation_errors.is_empty(), first) {
+        validation_errors.extend(validations.iter()
+            .filter(is_replay)
+            .map(|v| apply_validation(&registered, claims, headers, &time, v))
+            .filter_map(Result::err));
+    }
+
+    #[cfg(feature = "tracing")]
+    for failure in &validation_errors {
+        tracing::debug!(claim = failure.claim(), kind = failure.kind(), "claim validation failed");
+    }
 
     if validation_errors.is_empty() {
         Ok(())
@@ -0,0 +1,159 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+//! Defines the `MetricsSink` trait, through which validators and key
+//! stores report validation outcomes, key set refreshes and cache
+//! lookups.
+//!
+//! A reference implementation exporting Prometheus metrics is
+//! available if the `prometheus` feature is enabled.
+
+use std::time::Duration;
+
+use crate::ValidationError;
+
+/// Caches whose lookups are reported to a `MetricsSink`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetricsCache {
+    /// The cached key set of a `KeyStore`.
+    Keys,
+
+    /// The cache of validated tokens used by a `Validator`.
+    Tokens,
+}
+
+impl MetricsCache {
+    /// Return the name of the cache, e.g. for use as a metric label.
+    pub fn name(self) -> &'static str {
+        match self {
+            MetricsCache::Keys => "keys",
+            MetricsCache::Tokens => "tokens",
+        }
+    }
+}
+
+/// Receiver of metrics reported by a `Validator` or `KeyStore`, see
+/// `Validator::metrics` and `KeyStore::metrics`.
+///
+/// All functions have empty default implementations, so sinks only
+/// need to implement the ones they are interested in. They are called
+/// on the validation path and should not block.
+pub trait MetricsSink: Send + Sync {
+    /// Record the outcome of a token validation and the time it took,
+    /// including the time spent retrieving the key. Tokens served from
+    /// the token cache are reported as well.
+    ///
+    /// `ValidationError::kind` provides a label for failures.
+    fn validation(&self, _outcome: Result<(), &ValidationError>, _elapsed: Duration) {}
+
+    /// Record a fetch of a key set and its outcome. Responses to
+    /// conditional requests that indicate that the key set has not
+    /// been modified count as successful fetches.
+    fn key_refresh(&self, _outcome: Result<(), &ValidationError>) {}
+
+    /// Record a lookup in a cache, which can be used for computing the
+    /// hit rate of the cache.
+    fn cache_lookup(&self, _cache: MetricsCache, _hit: bool) {}
+}
+
+#[cfg(feature = "prometheus")]
+pub use self::prometheus_metrics::PrometheusMetrics;
+
+#[cfg(feature = "prometheus")]
+mod prometheus_metrics {
+    use prometheus::{Histogram, HistogramOpts, IntCounterVec, Opts, Registry};
+    use std::time::Duration;
+
+    use super::{MetricsCache, MetricsSink};
+    use crate::ValidationError;
+
+    /// Metrics sink that records metrics in a Prometheus registry.
+    ///
+    /// The following metrics are registered:
+    ///
+    /// * `alcoholic_jwt_validations_total`, labelled with the
+    ///   `outcome` (`accepted` or `rejected`) and the `error` kind of
+    ///   rejected tokens (empty for accepted tokens).
+    /// * `alcoholic_jwt_validation_duration_seconds`, a histogram of
+    ///   the time taken by validations.
+    /// * `alcoholic_jwt_key_refreshes_total`, labelled with the
+    ///   `outcome` (`success` or `failure`).
+    /// * `alcoholic_jwt_cache_lookups_total`, labelled with the `cache`
+    ///   (`keys` or `tokens`) and the `result` (`hit` or `miss`).
+    #[derive(Clone)]
+    pub struct PrometheusMetrics {
+        validations: IntCounterVec,
+        duration: Histogram,
+        refreshes: IntCounterVec,
+        lookups: IntCounterVec,
+    }
+
+    impl PrometheusMetrics {
+        /// Create the metrics and register them in the specified
+        /// registry.
+        ///
+        /// Registering fails if the registry already contains metrics
+        /// with the same names, e.g. from another instance. Instances
+        /// can be cloned to share the metrics instead.
+        pub fn new(registry: &Registry) -> prometheus::Result<PrometheusMetrics> {
+            let metrics = PrometheusMetrics {
+                validations: IntCounterVec::new(
+                    Opts::new("alcoholic_jwt_validations_total", "Number of validated tokens"),
+                    &["outcome", "error"],
+                )?,
+                duration: Histogram::with_opts(HistogramOpts::new(
+                    "alcoholic_jwt_validation_duration_seconds", "Time taken by token validations",
+                ))?,
+                refreshes: IntCounterVec::new(
+                    Opts::new("alcoholic_jwt_key_refreshes_total", "Number of key set fetches"),
+                    &["outcome"],
+                )?,
+                lookups: IntCounterVec::new(
+                    Opts::new("alcoholic_jwt_cache_lookups_total", "Number of cache lookups"),
+                    &["cache", "result"],
+                )?,
+            };
+
+            registry.register(Box::new(metrics.validations.clone()))?;
+            registry.register(Box::new(metrics.duration.clone()))?;
+            registry.register(Box::new(metrics.refreshes.clone()))?;
+            registry.register(Box::new(metrics.lookups.clone()))?;
+            Ok(metrics)
+        }
+    }
+
+    impl MetricsSink for PrometheusMetrics {
+        fn validation(&self, outcome: Result<(), &ValidationError>, elapsed: Duration) {
+            let labels = match outcome {
+                Ok(()) => ["accepted", ""],
+                Err(err) => ["rejected", err.kind()],
+            };
+
+            self.validations.with_label_values(&labels).inc();
+            self.duration.observe(elapsed.as_secs_f64());
+        }
+
+        fn key_refresh(&self, outcome: Result<(), &ValidationError>) {
+            let outcome = if outcome.is_ok() { "success" } else { "failure" };
+            self.refreshes.with_label_values(&[outcome]).inc();
+        }
+
+        fn cache_lookup(&self, cache: MetricsCache, hit: bool) {
+            let result = if hit { "hit" } else { "miss" };
+            self.lookups.with_label_values(&[cache.name(), result]).inc();
+        }
+    }
+}
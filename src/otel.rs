@@ -0,0 +1,116 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+//! Integration with OpenTelemetry traces.
+//!
+//! A `Validator` records the following attributes on the span that is
+//! active while it validates a token:
+//!
+//! * `jwt.alg` and `jwt.kid`: the algorithm and key ID from the header
+//!   of the token.
+//! * `jwt.issuer`: the issuer of the token, which is only recorded for
+//!   accepted tokens as the claims of rejected tokens can not be
+//!   trusted.
+//! * `jwt.outcome`: `accepted` or `rejected`.
+//! * `error.type`: for rejected tokens, the kind of the error as
+//!   returned by `ValidationError::kind`.
+//!
+//! Rejected tokens do not set the status of the span to an error, as
+//! rejecting tokens is usually not a failure of the operation that
+//! the span represents.
+//!
+//! The subject of a validated token can be added to the baggage of a
+//! context using `with_subject`, which propagates it to downstream
+//! services.
+
+use opentelemetry::baggage::{Baggage, BaggageExt};
+use opentelemetry::trace::get_active_span;
+use opentelemetry::{Context, KeyValue};
+use serde_json::Value;
+
+use crate::{token_header, JWTResult, ValidJWT};
+
+/// Baggage entry containing the subject of a token, following the
+/// `enduser.id` semantic convention.
+pub const SUBJECT_BAGGAGE: &str = "enduser.id";
+
+/// Record the outcome of a validation on the active span.
+pub(crate) fn record_validation(token: &str, result: &JWTResult<ValidJWT>) {
+    get_active_span(|span| {
+        if !span.is_recording() {
+            return;
+        }
+
+        let mut attributes = vec![];
+        match *result {
+            Ok(ref jwt) => {
+                let header = |name: &str| jwt.headers.get(name).and_then(Value::as_str).map(String::from);
+                attributes.extend(header("alg").map(|alg| KeyValue::new("jwt.alg", alg)));
+                attributes.extend(header("kid").map(|kid| KeyValue::new("jwt.kid", kid)));
+
+                if let Some(iss) = jwt.claims.get("iss").and_then(Value::as_str) {
+                    attributes.push(KeyValue::new("jwt.issuer", iss.to_string()));
+                }
+
+                attributes.push(KeyValue::new("jwt.outcome", "accepted"));
+            },
+
+            Err(ref err) => {
+                if let Ok(header) = token_header(token) {
+                    attributes.push(KeyValue::new("jwt.alg", header.alg));
+                    attributes.extend(header.kid.map(|kid| KeyValue::new("jwt.kid", kid)));
+                }
+
+                attributes.push(KeyValue::new("jwt.outcome", "rejected"));
+                attributes.push(KeyValue::new("error.type", err.kind()));
+            },
+        }
+
+        span.set_attributes(attributes);
+    })
+}
+
+/// Return a copy of the context whose baggage contains the subject of
+/// the validated token as `enduser.id`. The context is returned
+/// unchanged if the token does not have a subject.
+///
+/// Baggage is propagated to every downstream service, so this should
+/// only be used if the subject is not considered sensitive.
+///
+/// ```rust
+/// # use alcoholic_jwt::{otel, ValidJWT};
+/// # use opentelemetry::Context;
+/// fn handle_request(jwt: &ValidJWT) {
+///     let _guard = otel::with_subject(&Context::current(), jwt).attach();
+///     // ... calls to downstream services
+/// }
+/// ```
+pub fn with_subject(cx: &Context, jwt: &ValidJWT) -> Context {
+    match jwt.claims.get("sub").and_then(Value::as_str) {
+        Some(sub) => {
+            // Baggage replaces the existing entries of the context, so
+            // they are copied first.
+            let mut baggage = Baggage::new();
+            for (key, (value, metadata)) in cx.baggage() {
+                baggage.insert_with_metadata(key.clone(), value.clone(), metadata.clone());
+            }
+
+            baggage.insert(SUBJECT_BAGGAGE, sub.to_string());
+            cx.with_baggage(baggage)
+        },
+        None => cx.clone(),
+    }
+}
@@ -0,0 +1,127 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+//! Implements presets of the validations mandated by common token
+//! profiles, so that applications do not need to assemble them from
+//! the individual specifications.
+//!
+//! Presets are returned as sets of validations, which can be extended
+//! with further validations before use.
+
+use serde_json::Value;
+
+use crate::Validation;
+
+/// Member of the "events"-claim identifying logout tokens.
+const BACKCHANNEL_LOGOUT_EVENT: &str = "http://schemas.openid.net/event/backchannel-logout";
+
+impl Validation {
+    /// Construct the validations of an ID token as specified in
+    /// [OpenID Connect Core, section
+    /// 3.1.3.7](https://openid.net/specs/openid-connect-core-1_0.html#IDTokenValidation),
+    /// for the relying party with the specified client ID.
+    ///
+    /// ID tokens must be issued by the specified issuer for the client,
+    /// must identify the user ("sub"-claim), must not have expired and
+    /// must contain an "iat"-claim. A token with several audiences must
+    /// name the client in its "azp"-claim, which must match the client
+    /// whenever it is present.
+    ///
+    /// If a nonce was sent in the authentication request, the token
+    /// must contain the same nonce. Restrictions on the age of tokens
+    /// or on the authentication context can be added using
+    /// `Validation::IssuedAtMaxAge` and `Validation::OneOfAcrValues`.
+    pub fn oidc_id_token<I, C>(issuer: I, client_id: C, nonce: Option<&str>) -> Vec<Validation>
+    where I: Into<String>, C: Into<String> {
+        let client_id = client_id.into();
+        let mut validations = vec![
+            Validation::Issuer(issuer.into()),
+            Validation::Audience(client_id.clone()),
+            Validation::AuthorizedParty(client_id),
+            Validation::SubjectPresent,
+            Validation::NotExpired,
+            Validation::claims_present(["iat"]),
+            Validation::custom(|claims| {
+                let audiences = claims.get("aud").and_then(Value::as_array).map_or(1, Vec::len);
+                audiences <= 1 || claims.get("azp").is_some()
+            }),
+        ];
+
+        if let Some(nonce) = nonce {
+            validations.push(Validation::Nonce(nonce.into()));
+        }
+
+        validations
+    }
+
+    /// Construct the validations of an access token as specified in
+    /// the JWT profile for OAuth 2.0 access tokens ([RFC 9068, section
+    /// 4](https://tools.ietf.org/html/rfc9068#section-4)), for the
+    /// resource server with the specified resource identifier.
+    ///
+    /// Access tokens must have the type `at+jwt`, must be issued by the
+    /// specified issuer for the resource ("aud"-claim), must not have
+    /// expired and must contain the "sub", "client_id", "iat" and "jti"
+    /// claims.
+    ///
+    /// ```rust
+    /// # use alcoholic_jwt::{Validation, Validator};
+    /// let validator = Validator::new(
+    ///     Validation::jwt_access_token("https://auth.test.aprila.no", "https://api.test.aprila.no"),
+    /// );
+    /// ```
+    pub fn jwt_access_token<I, R>(issuer: I, resource: R) -> Vec<Validation>
+    where I: Into<String>, R: Into<String> {
+        vec![
+            Validation::TokenType("at+jwt".into()),
+            Validation::Issuer(issuer.into()),
+            Validation::Audience(resource.into()),
+            Validation::NotExpired,
+            Validation::SubjectPresent,
+            Validation::claims_present(["client_id", "iat", "jti"]),
+        ]
+    }
+
+    /// Construct the validations of a logout token as specified in
+    /// [OpenID Connect Back-Channel Logout, section
+    /// 2.6](https://openid.net/specs/openid-connect-backchannel-1_0.html#Validation),
+    /// for the relying party with the specified client ID.
+    ///
+    /// Logout tokens must be issued by the specified issuer for the
+    /// client, must not have expired, must contain the "iat", "jti" and
+    /// "events" claims with a logout event, must identify the session
+    /// using a "sub" or "sid" claim (or both), and must not contain a
+    /// "nonce"-claim, which distinguishes them from ID tokens.
+    ///
+    /// Adding `Validation::NoReplay` is recommended for rejecting
+    /// reused logout tokens.
+    pub fn logout_token<I, C>(issuer: I, client_id: C) -> Vec<Validation>
+    where I: Into<String>, C: Into<String> {
+        vec![
+            Validation::Issuer(issuer.into()),
+            Validation::Audience(client_id.into()),
+            Validation::NotExpired,
+            Validation::claims_present(["iat", "jti", "events"]),
+            Validation::AnyClaimPresent(vec!["sub".into(), "sid".into()]),
+            Validation::ClaimAbsent("nonce".into()),
+            Validation::custom(|claims| {
+                claims.get("events")
+                    .and_then(|events| events.get(BACKCHANNEL_LOGOUT_EVENT))
+                    .is_some_and(Value::is_object)
+            }),
+        ]
+    }
+}
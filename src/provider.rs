@@ -0,0 +1,127 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Defines the `KeyProvider` trait, which abstracts over the different
+//! sources of keys used for token validation.
+
+use std::borrow::Cow;
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::{FileKeyStore, JWK, JWKS, JWTResult, ValidationError};
+
+#[cfg(any(feature = "fetch", feature = "async"))]
+use crate::KeyStore;
+
+/// Boxed future returned by asynchronous trait methods.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// A source of keys that can be used for validating tokens.
+///
+/// This trait is implemented for plain key sets as well as for the
+/// key stores provided by this library, and can be implemented for
+/// any other source of keys (e.g. databases or hardware security
+/// modules).
+pub trait KeyProvider {
+    /// Return the signing key with the specified key ID.
+    ///
+    /// If no key ID is specified (i.e. the token does not contain a
+    /// `kid` header), providers should return their only signing key
+    /// and fail if there is more than one.
+    ///
+    /// Returns `ValidationError::KeyNotFound` if there is no suitable
+    /// key.
+    fn key_for(&self, kid: Option<&str>) -> JWTResult<Cow<'_, JWK>>;
+
+    /// Asynchronous equivalent of `KeyProvider::key_for`, for
+    /// providers that need to perform I/O to retrieve keys.
+    ///
+    /// The default implementation calls `KeyProvider::key_for`.
+    fn key_for_async<'a>(&'a self, kid: Option<&'a str>) -> BoxFuture<'a, JWTResult<Cow<'a, JWK>>> {
+        Box::pin(std::future::ready(self.key_for(kid)))
+    }
+}
+
+/// Select the signing key with the specified key ID from a key set,
+/// or its only signing key if no key ID is specified.
+fn select_key<'a>(jwks: &'a JWKS, kid: Option<&str>) -> JWTResult<&'a JWK> {
+    let found = match kid {
+        Some(kid) => jwks.find_signing_key(kid),
+        None => {
+            let mut signing_keys = jwks.keys().iter().filter(|jwk| jwk.is_signing_key());
+            match (signing_keys.next(), signing_keys.next()) {
+                (Some(jwk), None) => Some(jwk),
+                _ => None,
+            }
+        }
+    };
+
+    found.ok_or_else(|| ValidationError::KeyNotFound(kid.map(Into::into)))
+}
+
+impl KeyProvider for JWKS {
+    fn key_for(&self, kid: Option<&str>) -> JWTResult<Cow<'_, JWK>> {
+        select_key(self, kid).map(Cow::Borrowed)
+    }
+}
+
+impl KeyProvider for FileKeyStore {
+    fn key_for(&self, kid: Option<&str>) -> JWTResult<Cow<'_, JWK>> {
+        let jwks = self.keys()?;
+        select_key(&jwks, kid).map(|jwk| Cow::Owned(jwk.clone()))
+    }
+}
+
+/// Keys are fetched using a blocking HTTP client from `key_for`, and
+/// using an asynchronous HTTP client from `key_for_async`.
+///
+/// If the `fetch` feature is disabled, `key_for` only consults the
+/// cached key set and never fetches it.
+#[cfg(any(feature = "fetch", feature = "async"))]
+impl KeyProvider for KeyStore {
+    fn key_for(&self, kid: Option<&str>) -> JWTResult<Cow<'_, JWK>> {
+        #[cfg(feature = "fetch")]
+        let jwk = match kid {
+            Some(kid) => self.find(kid)?,
+            None => {
+                let jwks = self.keys()?;
+                select_key(&jwks, None)?.clone()
+            }
+        };
+
+        #[cfg(not(feature = "fetch"))]
+        let jwk = match self.cached() {
+            Some(jwks) => select_key(&jwks, kid)?.clone(),
+            None => return Err(ValidationError::KeyNotFound(kid.map(Into::into))),
+        };
+
+        Ok(Cow::Owned(jwk))
+    }
+
+    #[cfg(feature = "async")]
+    fn key_for_async<'a>(&'a self, kid: Option<&'a str>) -> BoxFuture<'a, JWTResult<Cow<'a, JWK>>> {
+        Box::pin(async move {
+            let jwk = match kid {
+                Some(kid) => self.find_async(kid).await?,
+                None => {
+                    let jwks = self.keys_async().await?;
+                    select_key(&jwks, None)?.clone()
+                }
+            };
+
+            Ok(Cow::Owned(jwk))
+        })
+    }
+}
@@ -0,0 +1,182 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+//! Presets for common identity providers, which derive the location
+//! of the key set and the validations required for their tokens from
+//! the provider-specific configuration.
+//!
+//! ```rust,no_run
+//! # use alcoholic_jwt::providers;
+//! let validator = providers::auth0("example.eu.auth0.com", "https://api.example.com").validator();
+//!
+//! # let token = "";
+//! let result = validator.validate(token);
+//! ```
+
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{KeyStore, Validation, Validator};
+
+/// Location of the key set used by Google for signing ID tokens.
+const GOOGLE_CERTS_URL: &str = "https://www.googleapis.com/oauth2/v3/certs";
+
+/// Issuers used by Google in ID tokens.
+const GOOGLE_ISSUERS: [&str; 2] = ["accounts.google.com", "https://accounts.google.com"];
+
+/// Lifetime of Google's key sets if a response does not specify one.
+/// Google publishes keys well before using them and serves its key
+/// sets with a `Cache-Control` lifetime of several hours, which is
+/// honoured if present.
+const GOOGLE_FALLBACK_TTL: Duration = Duration::from_secs(3600);
+
+/// Key store and validations for the tokens of an identity provider.
+///
+/// The validations can be extended (e.g. with `Validation::HasScope`)
+/// before constructing a validator, and the key store can be
+/// configured further, e.g. with a cache backend.
+pub struct Provider {
+    /// Key store for the key set published by the provider.
+    pub keys: KeyStore,
+
+    /// Validations that tokens of the provider must pass.
+    pub validations: Vec<Validation>,
+}
+
+impl Provider {
+    /// Construct a validator that validates tokens using the key store
+    /// and validations of the provider.
+    pub fn validator(self) -> Validator {
+        Validator::new(self.validations).key_provider(Arc::new(self.keys))
+    }
+}
+
+/// Strip the scheme and trailing slashes from a domain, which users
+/// often copy from their provider's dashboard as a URL.
+fn bare_domain(domain: &str) -> &str {
+    let domain = domain.strip_prefix("https://").unwrap_or(domain);
+    domain.trim_end_matches('/')
+}
+
+/// Preset for access tokens issued by an Auth0 tenant for an API.
+///
+/// `domain` is the domain of the tenant (e.g. `example.eu.auth0.com`
+/// or a custom domain) and `audience` is the identifier of the API.
+///
+/// Auth0 uses the URL of the tenant *including a trailing slash* as
+/// the issuer of its tokens (e.g. `https://example.eu.auth0.com/`),
+/// which this preset takes care of regardless of how the domain is
+/// specified. The key set is retrieved from
+/// `https://{domain}/.well-known/jwks.json`.
+pub fn auth0(domain: &str, audience: &str) -> Provider {
+    let domain = bare_domain(domain);
+
+    Provider {
+        keys: KeyStore::new(&format!("https://{}/.well-known/jwks.json", domain)),
+        validations: vec![
+            Validation::Issuer(format!("https://{}/", domain)),
+            Validation::Audience(audience.into()),
+            Validation::NotExpired,
+        ],
+    }
+}
+
+/// Preset for ID and access tokens issued by an AWS Cognito user pool
+/// for the app client with the specified ID.
+///
+/// The issuer of the tokens is
+/// `https://cognito-idp.{region}.amazonaws.com/{user_pool_id}`, whose
+/// key set is retrieved from `{issuer}/.well-known/jwks.json`.
+///
+/// Tokens must not have expired and must specify their type in the
+/// "token_use"-claim, which must be `id` or `access`. Cognito only
+/// includes the client in the "aud"-claim of ID tokens, while access
+/// tokens specify it in their "client_id"-claim; the claim matching
+/// the type of the token must contain the client ID. Add
+/// `Validation::claim_equals("token_use", "access")` to the
+/// validations to only accept access tokens.
+pub fn cognito(region: &str, user_pool_id: &str, client_id: &str) -> Provider {
+    let issuer = format!("https://cognito-idp.{}.amazonaws.com/{}", region, user_pool_id);
+    let client_id = client_id.to_string();
+
+    Provider {
+        keys: KeyStore::new(&format!("{}/.well-known/jwks.json", issuer)),
+        validations: vec![
+            Validation::Issuer(issuer),
+            Validation::NotExpired,
+            Validation::custom(move |claims| {
+                let client_claim = match claims.get("token_use").and_then(Value::as_str) {
+                    Some("id") => "aud",
+                    Some("access") => "client_id",
+                    _ => return false,
+                };
+
+                claims.get(client_claim).and_then(Value::as_str) == Some(client_id.as_str())
+            }),
+        ],
+    }
+}
+
+/// Preset for ID tokens signed by Google, i.e. tokens of Google
+/// Sign-In and tokens that Google issues to service accounts for
+/// calling other services (e.g. on Cloud Run).
+///
+/// `audience` is the OAuth client ID of the application for Google
+/// Sign-In, or the audience requested by the calling service (usually
+/// the URL of the receiving service) for service-to-service tokens.
+///
+/// Tokens must have been issued by `accounts.google.com` or
+/// `https://accounts.google.com`, both of which Google uses, for the
+/// audience and must not have expired. Applications that only accept
+/// users of a Google Workspace domain should add
+/// `Validation::claim_equals("hd", domain)`.
+///
+/// The key set is retrieved from Google's certificate endpoint and
+/// cached according to its `Cache-Control` header, so that rotated
+/// keys are picked up on schedule.
+pub fn google(audience: &str) -> Provider {
+    Provider {
+        keys: KeyStore::new(GOOGLE_CERTS_URL).fallback_ttl(GOOGLE_FALLBACK_TTL),
+        validations: vec![
+            Validation::one_of_issuers(GOOGLE_ISSUERS),
+            Validation::Audience(audience.into()),
+            Validation::NotExpired,
+        ],
+    }
+}
+
+/// Preset for tokens that a Google service account signs itself using
+/// one of its keys, e.g. for authenticating to services that are not
+/// hosted by Google.
+///
+/// The service account is the issuer of such tokens, and its public
+/// keys are retrieved from
+/// `https://www.googleapis.com/service_accounts/v1/jwk/{email}`.
+/// Tokens must be issued for the specified audience and must not have
+/// expired.
+pub fn google_service_account(email: &str, audience: &str) -> Provider {
+    let url = format!("https://www.googleapis.com/service_accounts/v1/jwk/{}", email);
+
+    Provider {
+        keys: KeyStore::new(&url).fallback_ttl(GOOGLE_FALLBACK_TTL),
+        validations: vec![
+            Validation::Issuer(email.into()),
+            Validation::Audience(audience.into()),
+            Validation::NotExpired,
+        ],
+    }
+}
@@ -0,0 +1,113 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Implements replay detection for one-time-use tokens based on their
+//! "jti"-claim.
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// Record of token IDs that have already been used, consulted by
+/// `Validation::NoReplay`.
+///
+/// Implementations can be backed by shared storage (e.g. a database)
+/// to detect replays across several instances of an application.
+pub trait ReplayCache {
+    /// Record the use of the token with the specified ID and return
+    /// whether it had already been used before.
+    ///
+    /// The expiry time of the token is passed along, after which the
+    /// record is no longer required as the token is rejected anyway.
+    /// Checking and recording must happen atomically.
+    fn seen(&self, jti: &str, exp: Option<SystemTime>) -> bool;
+
+    /// Record the use of a token as in `ReplayCache::seen`, at the
+    /// specified time. `Validation::NoReplay` passes the time used by
+    /// all time-based validations, see `Validation::TimeSource`.
+    ///
+    /// The default implementation ignores the time and calls
+    /// `ReplayCache::seen`.
+    fn seen_at(&self, jti: &str, exp: Option<SystemTime>, _now: SystemTime) -> bool {
+        self.seen(jti, exp)
+    }
+}
+
+/// Replay cache that keeps token IDs in memory until the tokens
+/// expire. Token IDs of tokens without an expiry time are kept
+/// forever.
+#[derive(Default)]
+pub struct InMemoryReplayCache {
+    seen: Mutex<ReplayRecords>,
+}
+
+/// Token IDs recorded by an `InMemoryReplayCache`.
+#[derive(Default)]
+struct ReplayRecords {
+    /// Expiry times of all recorded token IDs.
+    ids: HashMap<String, Option<SystemTime>>,
+
+    /// Recorded token IDs that have an expiry time, ordered by it.
+    expiries: BTreeSet<(SystemTime, String)>,
+}
+
+impl InMemoryReplayCache {
+    /// Create an empty replay cache.
+    pub fn new() -> InMemoryReplayCache {
+        InMemoryReplayCache::default()
+    }
+
+    /// Return the number of token IDs currently recorded.
+    pub fn len(&self) -> usize {
+        self.seen.lock().unwrap().ids.len()
+    }
+
+    /// Check whether no token IDs are currently recorded.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl ReplayCache for InMemoryReplayCache {
+    fn seen(&self, jti: &str, exp: Option<SystemTime>) -> bool {
+        self.seen_at(jti, exp, SystemTime::now())
+    }
+
+    fn seen_at(&self, jti: &str, exp: Option<SystemTime>, now: SystemTime) -> bool {
+        let mut records = self.seen.lock().unwrap();
+
+        // Expired tokens can not be replayed, so their IDs are removed
+        // in order of expiry until the first one that is still valid.
+        while let Some((expires_at, id)) = records.expiries.pop_first() {
+            if expires_at > now {
+                records.expiries.insert((expires_at, id));
+                break;
+            }
+
+            records.ids.remove(&id);
+        }
+
+        if records.ids.contains_key(jti) {
+            return true;
+        }
+
+        records.ids.insert(jti.into(), exp);
+        if let Some(exp) = exp {
+            records.expiries.insert((exp, jti.into()));
+        }
+
+        false
+    }
+}
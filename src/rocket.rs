@@ -0,0 +1,103 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+//! Integration with [Rocket](https://rocket.rs), which is available if
+//! the `rocket` feature of this library is enabled.
+//!
+//! The `Jwt` request guard validates the bearer token of a request
+//! using the `Validator` managed by the application, and deserialises
+//! its claims into the specified type:
+//!
+//! ```rust,no_run
+//! # #[macro_use] extern crate rocket;
+//! # use alcoholic_jwt::rocket::Jwt;
+//! # use alcoholic_jwt::{Validation, Validator};
+//! # use serde_derive::Deserialize;
+//! #[derive(Deserialize)]
+//! struct User {
+//!     sub: String,
+//! }
+//!
+//! #[get("/profile")]
+//! fn profile(user: Jwt<User>) -> String {
+//!     format!("Hello, {}!", user.0.sub)
+//! }
+//!
+//! # fn main() {
+//! # let validator = Validator::new(vec![Validation::NotExpired]);
+//! let rocket = rocket::build()
+//!     .manage(validator)
+//!     .mount("/", routes![profile]);
+//! # }
+//! ```
+//!
+//! Requests without a valid token fail with `401 Unauthorized`, and
+//! requests with a token lacking a scope required by the validator
+//! (see `Validation::HasScope`) with `403 Forbidden`. Catchers can add
+//! the `WWW-Authenticate` header of the failure using `Challenge`.
+
+use ::rocket::http::Status;
+use ::rocket::request::{FromRequest, Outcome, Request};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::auth::authenticate;
+use crate::{AuthError, ValidJWT, Validator};
+
+/// Request guard for the validated token of a request, whose claims
+/// are deserialised into the specified type (the full claim set by
+/// default).
+///
+/// Tokens whose claims can not be deserialised are rejected like
+/// invalid tokens.
+#[derive(Clone, Debug)]
+pub struct Jwt<T = Value>(pub T);
+
+/// Value of the `WWW-Authenticate` header for a request rejected by
+/// the `Jwt` guard, which catchers can retrieve from the request:
+///
+/// ```rust
+/// # use alcoholic_jwt::rocket::Challenge;
+/// # fn example(req: &rocket::Request<'_>) {
+/// let challenge = req.local_cache(|| Challenge(None));
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Challenge(pub Option<String>);
+
+#[::rocket::async_trait]
+impl<'r, T: DeserializeOwned> FromRequest<'r> for Jwt<T> {
+    type Error = AuthError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Jwt<T>, AuthError> {
+        let result = match req.rocket().state::<Validator>() {
+            Some(validator) => authenticate(validator, req.headers().get_one("Authorization"), &[]).await,
+            None => Err(AuthError::MissingValidator),
+        };
+
+        let result = result.and_then(|jwt: ValidJWT| {
+            serde_json::from_value(jwt.claims).map_err(|err| AuthError::InvalidToken(err.into()))
+        });
+
+        match result {
+            Ok(claims) => Outcome::Success(Jwt(claims)),
+            Err(err) => {
+                req.local_cache(|| Challenge(err.www_authenticate()));
+                Outcome::Error((Status::new(err.status()), err))
+            },
+        }
+    }
+}
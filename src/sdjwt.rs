@@ -0,0 +1,258 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+//! Implements verification of SD-JWT presentations as specified in
+//! [RFC 9901](https://www.rfc-editor.org/rfc/rfc9901).
+//!
+//! An SD-JWT consists of an issuer-signed JWT, whose claims contain
+//! digests of selectively disclosable claims in place of the claims
+//! themselves, and disclosures of some of these claims chosen by the
+//! holder. The disclosures are transmitted together with the JWT,
+//! separated by tildes (`<JWT>~<disclosure>~...~`).
+
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    deserialize_part, token_limits, token_parts, validate_claim_set, verify_signature,
+    with_token_kid, ClaimFailure, JWTResult, KeyProvider, ValidJWT, Validation,
+    ValidationError, JWT,
+};
+
+/// Representation of a single disclosure of an SD-JWT.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Disclosure {
+    /// The name of the disclosed claim, or `None` for disclosed array
+    /// elements.
+    pub name: Option<String>,
+
+    /// The disclosed value.
+    pub value: Value,
+
+    /// The digest of the disclosure, as referenced by the
+    /// issuer-signed JWT.
+    pub digest: String,
+}
+
+impl Disclosure {
+    /// Decode a disclosure in its encoded form, i.e. a base64url-encoded
+    /// JSON array of a salt, the claim name (for object members) and
+    /// the claim value.
+    fn parse(encoded: &str) -> JWTResult<Disclosure> {
+        let elements: Vec<Value> = deserialize_part(encoded)?;
+        let (name, value) = match elements.as_slice() {
+            [Value::String(_), value] => (None, value.clone()),
+            [Value::String(_), Value::String(name), value] => (Some(name.clone()), value.clone()),
+            _ => return Err(ValidationError::InvalidComponents),
+        };
+
+        // These names are reserved for the digests in the JWT.
+        if matches!(name.as_deref(), Some("_sd") | Some("...")) {
+            return Err(sd_failure("disclosure uses a reserved claim name"));
+        }
+
+        let digest = base64::encode_config(&openssl::sha::sha256(encoded.as_bytes()), base64::URL_SAFE_NO_PAD);
+        Ok(Disclosure { name, value, digest })
+    }
+}
+
+/// Representation of a verified SD-JWT presentation.
+#[derive(Clone, Debug)]
+pub struct SDJWT {
+    /// The issuer-signed JWT, whose claims are the reconstructed claim
+    /// set containing all disclosed claims.
+    pub jwt: ValidJWT,
+
+    /// The disclosures contained in the presentation.
+    pub disclosures: Vec<Disclosure>,
+
+    /// The key binding JWT of the presentation, if any.
+    ///
+    /// This JWT is *not* validated by `validate_sd_jwt`. Verifiers
+    /// requiring key binding must validate it using the key specified
+    /// in the "cnf"-claim of the issuer-signed JWT.
+    pub key_binding_jwt: Option<String>,
+}
+
+/// Verify an SD-JWT presentation (`<JWT>~<disclosure>~...~`, optionally
+/// followed by a key binding JWT) and reconstruct its claims.
+///
+/// The issuer-signed JWT is verified using a key from the specified
+/// key provider, and each disclosure must be referenced by a digest in
+/// its claims. Only the `sha-256` digest algorithm is supported.
+///
+/// The specified validations are applied to the reconstructed claim
+/// set, in which undisclosed claims are absent.
+pub fn validate_sd_jwt<P>(presentation: &str,
+                          provider: &P,
+                          validations: Vec<Validation>) -> JWTResult<SDJWT>
+where P: KeyProvider + ?Sized {
+    let limits = token_limits(&validations);
+    if presentation.len() > limits.max_token_length {
+        return Err(ValidationError::LimitExceeded("max_token_length"));
+    }
+
+    // The JWT is followed by a tilde-terminated list of disclosures
+    // and the (possibly empty) key binding JWT.
+    let mut components: Vec<&str> = presentation.split('~').collect();
+    if components.len() < 2 {
+        return Err(ValidationError::InvalidComponents);
+    }
+
+    let issuer_jwt = components.remove(0);
+    let key_binding_jwt = components.pop()
+        .filter(|kb| !kb.is_empty())
+        .map(String::from);
+
+    let jwk = with_token_kid(issuer_jwt, &limits, |kid| provider.key_for(kid))?;
+    verify_signature(&JWT(issuer_jwt), jwk.public_key()?)?;
+
+    let [header_b64, claims_b64, _] = token_parts(issuer_jwt)?;
+    let headers: Value = deserialize_part(header_b64)?;
+    let mut claims: Map<String, Value> = deserialize_part(claims_b64)?;
+
+    match claims.remove("_sd_alg") {
+        None => {},
+        Some(ref alg) if alg == "sha-256" => {},
+        Some(alg) => return Err(ValidationError::DisallowedAlgorithm(alg.as_str().map(String::from))),
+    }
+
+    let disclosures = components.into_iter()
+        .map(Disclosure::parse)
+        .collect::<JWTResult<Vec<Disclosure>>>()?;
+
+    let mut unused: HashMap<&str, &Disclosure> = HashMap::new();
+    for disclosure in &disclosures {
+        if unused.insert(disclosure.digest.as_str(), disclosure).is_some() {
+            return Err(sd_failure("disclosure is included more than once"));
+        }
+    }
+
+    let mut claims = Value::Object(claims);
+    disclose(&mut claims, &mut unused, &mut HashSet::new())?;
+
+    if !unused.is_empty() {
+        return Err(sd_failure("disclosure is not referenced by the token"));
+    }
+
+    validate_claim_set(&headers, &claims, &validations)?;
+
+    Ok(SDJWT {
+        jwt: ValidJWT { headers, claims },
+        disclosures,
+        key_binding_jwt,
+    })
+}
+
+/// Construct the error returned for invalid selective disclosures.
+fn sd_failure(reason: &'static str) -> ValidationError {
+    ValidationError::InvalidClaims(vec![ClaimFailure::invalid("_sd", reason)])
+}
+
+/// Replace the digests in a value with the claims they disclose,
+/// removing each used disclosure from the set of unused disclosures.
+///
+/// Digests without a disclosure are removed, as they refer to claims
+/// that have not been disclosed (or are decoys). Every digest is
+/// recorded in the set of seen digests, and digests that appear more
+/// than once are rejected.
+fn disclose(value: &mut Value,
+            unused: &mut HashMap<&str, &Disclosure>,
+            seen: &mut HashSet<String>) -> JWTResult<()> {
+    match value {
+        Value::Object(object) => {
+            let digests = match object.remove("_sd") {
+                None => vec![],
+                Some(Value::Array(digests)) => digests,
+                Some(_) => return Err(sd_failure("must be a list of digests")),
+            };
+
+            for member in object.values_mut() {
+                disclose(member, unused, seen)?;
+            }
+
+            for digest in digests {
+                let digest = digest.as_str().ok_or_else(|| sd_failure("must be a list of digests"))?;
+                check_digest(digest, seen)?;
+                let disclosure = match unused.remove(digest) {
+                    Some(disclosure) => disclosure,
+                    None => continue,
+                };
+
+                let name = disclosure.name.as_ref()
+                    .ok_or_else(|| sd_failure("array element disclosed as object member"))?;
+
+                if object.contains_key(name) {
+                    return Err(sd_failure("disclosed claim already exists"));
+                }
+
+                let mut disclosed = disclosure.value.clone();
+                disclose(&mut disclosed, unused, seen)?;
+                object.insert(name.clone(), disclosed);
+            }
+        },
+
+        Value::Array(elements) => {
+            let mut disclosed = Vec::with_capacity(elements.len());
+            for mut element in elements.drain(..) {
+                match array_digest(&element) {
+                    Some(Value::String(digest)) => {
+                        check_digest(digest, seen)?;
+                        if let Some(disclosure) = unused.remove(digest.as_str()) {
+                            if disclosure.name.is_some() {
+                                return Err(sd_failure("object member disclosed as array element"));
+                            }
+
+                            let mut value = disclosure.value.clone();
+                            disclose(&mut value, unused, seen)?;
+                            disclosed.push(value);
+                        }
+                    },
+                    Some(_) => return Err(sd_failure("array element digest must be a string")),
+                    None => {
+                        disclose(&mut element, unused, seen)?;
+                        disclosed.push(element);
+                    },
+                }
+            }
+
+            *elements = disclosed;
+        },
+
+        _ => {},
+    }
+
+    Ok(())
+}
+
+/// Record a digest of the token, rejecting digests that have been
+/// seen before.
+fn check_digest(digest: &str, seen: &mut HashSet<String>) -> JWTResult<()> {
+    if seen.insert(digest.into()) {
+        Ok(())
+    } else {
+        Err(sd_failure("digest appears more than once"))
+    }
+}
+
+/// Return the digest of a selectively disclosable array element,
+/// which is represented as an object with a single "..." member.
+fn array_digest(element: &Value) -> Option<&Value> {
+    match element.as_object() {
+        Some(object) if object.len() == 1 => object.get("..."),
+        _ => None,
+    }
+}
@@ -0,0 +1,250 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Implements signing of tokens using `RS256`, the counterpart of
+//! token validation for services that issue tokens themselves.
+
+use openssl::bn::{BigNum, BigNumRef};
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Private};
+use openssl::rsa::Rsa;
+use openssl::sign::Signer;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::{jwt_forgiving, JWK, JWTResult, KeyPolicy, KeyType, ValidationError};
+
+/// Representation of a private RSA key in JWK format. See [RFC 7518,
+/// section 6.3](https://tools.ietf.org/html/rfc7518#section-6.3).
+///
+/// Only two-prime keys containing all private key members, including
+/// the CRT parameters, are supported.
+///
+/// The private members are overwritten with zeroes when the key is
+/// dropped and are omitted from its `Debug` output. Copies made while
+/// deserialising the key (e.g. by the JSON parser) are outside of the
+/// control of this library.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct PrivateJWK {
+    kty: KeyType,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kid: Option<String>,
+
+    // Public members of the key
+    n: String,
+    e: String,
+
+    // Private exponent
+    d: String,
+
+    // Prime factors and CRT parameters
+    p: String,
+    q: String,
+    dp: String,
+    dq: String,
+    qi: String,
+}
+
+impl fmt::Debug for PrivateJWK {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PrivateJWK")
+            .field("kty", &self.kty)
+            .field("kid", &self.kid)
+            .field("n", &self.n)
+            .field("e", &self.e)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for PrivateJWK {
+    fn drop(&mut self) {
+        for member in [&mut self.d, &mut self.p, &mut self.q, &mut self.dp, &mut self.dq, &mut self.qi] {
+            member.zeroize();
+        }
+    }
+}
+
+impl PrivateJWK {
+    /// Construct a private JWK from an OpenSSL RSA private key.
+    ///
+    /// Returns `ValidationError::InvalidJWK` if the key is not an RSA
+    /// key or lacks the CRT parameters.
+    pub fn from_pkey(key: &PKey<Private>, kid: Option<String>) -> JWTResult<PrivateJWK> {
+        let rsa = key.rsa().map_err(|_| ValidationError::InvalidJWK)?;
+        let encode = |member: Option<&BigNumRef>| -> JWTResult<String> {
+            let bytes = Zeroizing::new(member.ok_or(ValidationError::InvalidJWK)?.to_vec());
+            Ok(base64::encode_config(&*bytes, base64::URL_SAFE_NO_PAD))
+        };
+
+        Ok(PrivateJWK {
+            kty: KeyType::RSA,
+            kid,
+            n: encode(Some(rsa.n()))?,
+            e: encode(Some(rsa.e()))?,
+            d: encode(Some(rsa.d()))?,
+            p: encode(rsa.p())?,
+            q: encode(rsa.q())?,
+            dp: encode(rsa.dmp1())?,
+            dq: encode(rsa.dmq1())?,
+            qi: encode(rsa.iqmp())?,
+        })
+    }
+
+    /// Return the key ID of this key, if any.
+    pub fn kid(&self) -> Option<&str> {
+        self.kid.as_deref()
+    }
+
+    /// Return the public part of this key, e.g. for publishing it in
+    /// a key set.
+    pub fn public_jwk(&self) -> JWK {
+        JWK::new_rsa(&self.n, &self.e, self.kid.clone())
+    }
+
+    /// Construct the OpenSSL private key represented by this JWK.
+    ///
+    /// Returns `ValidationError::InvalidJWK` if the key members can
+    /// not be decoded.
+    pub fn to_pkey(&self) -> JWTResult<PKey<Private>> {
+        let decode = |member: &str| -> JWTResult<BigNum> {
+            let bytes = Zeroizing::new(base64::decode_config(member, jwt_forgiving())
+                .map_err(|_| ValidationError::InvalidJWK)?);
+            BigNum::from_slice(&bytes).map_err(Into::into)
+        };
+
+        let rsa = Rsa::from_private_components(
+            decode(&self.n)?, decode(&self.e)?, decode(&self.d)?,
+            decode(&self.p)?, decode(&self.q)?,
+            decode(&self.dp)?, decode(&self.dq)?, decode(&self.qi)?,
+        )?;
+
+        PKey::from_rsa(rsa).map_err(Into::into)
+    }
+}
+
+/// Generate a new RSA key pair with a modulus of the specified size,
+/// which must be at least 2048 bits (see `KeyPolicy`).
+///
+/// Both keys use the JWK thumbprint of the public key as their key ID.
+/// The public key can be published in a key set, e.g. by collecting
+/// keys into a `JWKS`.
+///
+/// Returns `ValidationError::WeakKey` if the key size is too small.
+pub fn generate_rsa(bits: u32) -> JWTResult<(PrivateJWK, JWK)> {
+    if bits < KeyPolicy::default().min_rsa_bits {
+        return Err(ValidationError::WeakKey(bits));
+    }
+
+    let key = PKey::from_rsa(Rsa::generate(bits)?)?;
+    let mut private = PrivateJWK::from_pkey(&key, None)?;
+    let kid = private.public_jwk().thumbprint();
+    private.kid = Some(kid);
+
+    let public = private.public_jwk();
+    Ok((private, public))
+}
+
+/// Sign a set of claims using the specified private key and return
+/// the resulting token in compact serialisation.
+///
+/// The "alg"-header is always set to `RS256`. The key ID of the key
+/// is added as the "kid"-header unless the supplied headers already
+/// contain one, and the "typ"-header defaults to `JWT`.
+///
+/// ```rust
+/// # use alcoholic_jwt::{sign, PrivateJWK, ValidationError};
+/// # fn example(key: &PrivateJWK) -> Result<String, ValidationError> {
+/// let claims = serde_json::json!({"iss": "auth.test.aprila.no", "sub": "42"});
+/// let token = sign(&claims, key, serde_json::Map::new())?;
+/// # Ok(token)
+/// # }
+/// ```
+pub fn sign<C: Serialize>(claims: &C,
+                          key: &PrivateJWK,
+                          headers: Map<String, Value>) -> JWTResult<String> {
+    let mut headers = headers;
+    headers.insert("alg".into(), "RS256".into());
+    headers.entry("typ").or_insert_with(|| "JWT".into());
+    if let Some(kid) = key.kid() {
+        headers.entry("kid").or_insert_with(|| kid.into());
+    }
+
+    sign_unchecked(&headers, claims, key)
+}
+
+/// Construct a client assertion for the `private_key_jwt` client
+/// authentication method of OAuth 2.0 and OpenID Connect, as specified
+/// in [RFC 7523, section 3](https://tools.ietf.org/html/rfc7523#section-3).
+///
+/// The assertion is issued by and for the specified client (the "iss"
+/// and "sub" claims), intended for the specified token endpoint (the
+/// "aud" claim), expires after the specified lifetime and has a
+/// random token ID (the "jti" claim).
+///
+/// ```rust
+/// # use alcoholic_jwt::{client_assertion, PrivateJWK, ValidationError};
+/// # use std::time::Duration;
+/// # fn example(key: &PrivateJWK) -> Result<(), ValidationError> {
+/// let assertion = client_assertion("my-client", "https://auth.test.aprila.no/token",
+///                                  key, Duration::from_secs(60))?;
+/// // ... sent as the "client_assertion" parameter of a token request,
+/// // with a "client_assertion_type" of
+/// // "urn:ietf:params:oauth:client-assertion-type:jwt-bearer".
+/// # Ok(())
+/// # }
+/// ```
+pub fn client_assertion(client_id: &str,
+                        token_endpoint: &str,
+                        key: &PrivateJWK,
+                        lifetime: Duration) -> JWTResult<String> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time is likely incorrect")
+        .as_secs();
+
+    let mut jti = [0; 16];
+    openssl::rand::rand_bytes(&mut jti)?;
+
+    let claims = json!({
+        "iss": client_id,
+        "sub": client_id,
+        "aud": token_endpoint,
+        "iat": now,
+        "exp": now.saturating_add(lifetime.as_secs()),
+        "jti": base64::encode_config(&jti, base64::URL_SAFE_NO_PAD),
+    });
+
+    sign(&claims, key, Map::new())
+}
+
+/// Sign a token with the exact headers supplied, which may specify an
+/// algorithm other than the `RS256` signature that is computed.
+pub(crate) fn sign_unchecked<C: Serialize>(headers: &Map<String, Value>,
+                                           claims: &C,
+                                           key: &PrivateJWK) -> JWTResult<String> {
+    let encode = |bytes: &[u8]| base64::encode_config(bytes, base64::URL_SAFE_NO_PAD);
+    let data = format!("{}.{}", encode(&serde_json::to_vec(headers)?), encode(&serde_json::to_vec(claims)?));
+
+    let pkey = key.to_pkey()?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &pkey)?;
+    signer.update(data.as_bytes())?;
+    let signature = signer.sign_to_vec()?;
+
+    Ok(format!("{}.{}", data, encode(&signature)))
+}
@@ -0,0 +1,371 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Utilities for testing code that validates tokens, such as request
+//! handlers of services.
+//!
+//! A `TestIssuer` holds a throwaway key pair, whose public key is
+//! exposed as a key set and whose private key signs test tokens with
+//! arbitrary claims:
+//!
+//! ```rust
+//! use alcoholic_jwt::{validate_with, Validation};
+//! use alcoholic_jwt::test_utils::TestIssuer;
+//! use std::time::Duration;
+//!
+//! let issuer = TestIssuer::new();
+//! let token = issuer.token()
+//!     .claim("sub", "42")
+//!     .expires_in(Duration::from_secs(60))
+//!     .sign();
+//!
+//! let jwt = validate_with(&token, issuer.jwks(), vec![Validation::NotExpired]).unwrap();
+//! assert_eq!("42", jwt.claims["sub"]);
+//! ```
+//!
+//! A `MockJWKSServer` publishes the keys of test issuers over HTTP and
+//! can rotate them, for testing key retrieval.
+//!
+//! This module is only available if the `test-utils` feature is
+//! enabled, and is not meant to be used outside of tests.
+
+use serde_json::{Map, Value};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::signing::sign_unchecked;
+use crate::{generate_rsa, JWKS, PrivateJWK};
+
+/// Default lifetime of test tokens.
+const DEFAULT_LIFETIME: Duration = Duration::from_secs(3600);
+
+/// Issuer of test tokens with a freshly generated key pair.
+pub struct TestIssuer {
+    key: PrivateJWK,
+    jwks: JWKS,
+}
+
+impl Default for TestIssuer {
+    fn default() -> Self {
+        TestIssuer::new()
+    }
+}
+
+impl TestIssuer {
+    /// Create an issuer with a new 2048-bit RSA key pair.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key pair can not be generated.
+    pub fn new() -> TestIssuer {
+        let (key, public) = generate_rsa(2048).expect("failed to generate test key pair");
+        TestIssuer { key, jwks: std::iter::once(public).collect() }
+    }
+
+    /// Return the key set containing the public key of this issuer.
+    pub fn jwks(&self) -> &JWKS {
+        &self.jwks
+    }
+
+    /// Return the private key of this issuer.
+    pub fn private_key(&self) -> &PrivateJWK {
+        &self.key
+    }
+
+    /// Return the key ID of the key pair, which is added to the
+    /// "kid"-header of tokens by default.
+    pub fn kid(&self) -> &str {
+        self.key.kid().unwrap_or_default()
+    }
+
+    /// Start building a token signed by this issuer.
+    ///
+    /// Tokens use the `RS256` algorithm and the key ID of this issuer,
+    /// are issued now and expire in one hour unless specified
+    /// otherwise.
+    pub fn token(&self) -> TestToken<'_> {
+        let now = unix_now();
+        let mut headers = Map::new();
+        headers.insert("alg".into(), "RS256".into());
+        headers.insert("typ".into(), "JWT".into());
+        headers.insert("kid".into(), self.kid().into());
+
+        let mut claims = Map::new();
+        claims.insert("iat".into(), now.into());
+        claims.insert("exp".into(), (now + DEFAULT_LIFETIME.as_secs()).into());
+
+        TestToken { issuer: self, headers, claims }
+    }
+}
+
+/// Builder for a test token, see `TestIssuer::token`.
+#[must_use]
+pub struct TestToken<'a> {
+    issuer: &'a TestIssuer,
+    headers: Map<String, Value>,
+    claims: Map<String, Value>,
+}
+
+impl TestToken<'_> {
+    /// Set a claim of the token.
+    pub fn claim<V: Into<Value>>(mut self, name: &str, value: V) -> Self {
+        self.claims.insert(name.into(), value.into());
+        self
+    }
+
+    /// Set all claims contained in a JSON object, e.g. one constructed
+    /// using `serde_json::json!`. Other values are ignored.
+    pub fn claims(mut self, claims: Value) -> Self {
+        if let Value::Object(claims) = claims {
+            self.claims.extend(claims);
+        }
+        self
+    }
+
+    /// Remove a claim, e.g. one of the default "iat" and "exp" claims.
+    pub fn without_claim(mut self, name: &str) -> Self {
+        self.claims.remove(name);
+        self
+    }
+
+    /// Let the token expire after the specified duration.
+    pub fn expires_in(self, lifetime: Duration) -> Self {
+        self.claim("exp", unix_now() + lifetime.as_secs())
+    }
+
+    /// Let the token have expired the specified duration ago.
+    pub fn expired_for(self, duration: Duration) -> Self {
+        self.claim("exp", unix_now().saturating_sub(duration.as_secs()))
+    }
+
+    /// Set a header of the token.
+    pub fn header<V: Into<Value>>(mut self, name: &str, value: V) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+
+    /// Set the key ID of the token, or remove it.
+    pub fn kid(mut self, kid: Option<&str>) -> Self {
+        match kid {
+            Some(kid) => self.headers.insert("kid".into(), kid.into()),
+            None => self.headers.remove("kid"),
+        };
+        self
+    }
+
+    /// Set the "alg"-header of the token. The token is still signed
+    /// using `RS256`, which allows testing the rejection of tokens
+    /// with unexpected algorithms.
+    pub fn alg(self, alg: &str) -> Self {
+        self.header("alg", alg)
+    }
+
+    /// Sign the token and return it in compact serialisation.
+    ///
+    /// # Panics
+    ///
+    /// Panics if signing fails.
+    pub fn sign(self) -> String {
+        sign_unchecked(&self.headers, &self.claims, &self.issuer.key)
+            .expect("failed to sign test token")
+    }
+}
+
+/// Current time in seconds since the UNIX epoch.
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_secs()).unwrap_or_default()
+}
+
+/// Shared state of a `MockJWKSServer`.
+struct ServerState {
+    /// Issuers whose keys are published, the current one last.
+    issuers: Vec<Arc<TestIssuer>>,
+    cache_control: Option<String>,
+    status: u16,
+    requests: usize,
+}
+
+/// Minimal HTTP server that publishes the keys of one or more test
+/// issuers, for testing key retrieval and key rotation (e.g. with a
+/// `KeyStore`).
+///
+/// Every request is answered with the current key set, regardless of
+/// its path. The server stops when it is dropped.
+///
+/// ```rust
+/// use alcoholic_jwt::test_utils::MockJWKSServer;
+///
+/// let server = MockJWKSServer::start();
+/// server.set_cache_control(Some("max-age=60"));
+///
+/// let old = server.current();
+/// let new = server.rotate();
+/// // ... keys of both issuers are published until the old one is retired:
+/// server.retire_previous();
+/// ```
+pub struct MockJWKSServer {
+    url: String,
+    state: Arc<Mutex<ServerState>>,
+    stopped: Arc<AtomicBool>,
+    address: SocketAddr,
+}
+
+impl MockJWKSServer {
+    /// Start a server on a random local port, publishing the key of a
+    /// newly generated test issuer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the server can not be started.
+    pub fn start() -> MockJWKSServer {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind mock JWKS server");
+        let address = listener.local_addr().expect("failed to determine mock JWKS server address");
+
+        let state = Arc::new(Mutex::new(ServerState {
+            issuers: vec![Arc::new(TestIssuer::new())],
+            cache_control: None,
+            status: 200,
+            requests: 0,
+        }));
+
+        let stopped = Arc::new(AtomicBool::new(false));
+        let server = MockJWKSServer {
+            url: format!("http://{}/.well-known/jwks.json", address),
+            state: state.clone(),
+            stopped: stopped.clone(),
+            address,
+        };
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+
+                // Failed connections only affect the client that made
+                // them.
+                if let Ok(stream) = stream {
+                    let _ = respond(stream, &state);
+                }
+            }
+        });
+
+        server
+    }
+
+    /// Return the URL of the published key set.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Return the current issuer, whose key was published last.
+    pub fn current(&self) -> Arc<TestIssuer> {
+        let state = self.state.lock().unwrap();
+        state.issuers.last().expect("mock JWKS server has no issuers").clone()
+    }
+
+    /// Rotate keys by publishing the key of a newly generated issuer,
+    /// which becomes the current issuer. Previously published keys
+    /// remain in the key set until they are retired.
+    pub fn rotate(&self) -> Arc<TestIssuer> {
+        let issuer = Arc::new(TestIssuer::new());
+        self.state.lock().unwrap().issuers.push(issuer.clone());
+        issuer
+    }
+
+    /// Stop publishing all keys except for the key of the current
+    /// issuer.
+    pub fn retire_previous(&self) {
+        let mut state = self.state.lock().unwrap();
+        let current = state.issuers.len() - 1;
+        state.issuers.drain(..current);
+    }
+
+    /// Return the key set as currently published.
+    pub fn jwks(&self) -> JWKS {
+        let state = self.state.lock().unwrap();
+        published(&state)
+    }
+
+    /// Set the `Cache-Control` header of responses, or remove it.
+    pub fn set_cache_control(&self, cache_control: Option<&str>) {
+        self.state.lock().unwrap().cache_control = cache_control.map(Into::into);
+    }
+
+    /// Set the status of responses, e.g. `503` for simulating an
+    /// unavailable identity provider. Responses with a status other
+    /// than `200` have an empty body.
+    pub fn set_status(&self, status: u16) {
+        self.state.lock().unwrap().status = status;
+    }
+
+    /// Return the number of requests received so far.
+    pub fn requests(&self) -> usize {
+        self.state.lock().unwrap().requests
+    }
+}
+
+impl Drop for MockJWKSServer {
+    fn drop(&mut self) {
+        // The listener is woken up with a final connection, after which
+        // the server thread stops.
+        self.stopped.store(true, Ordering::SeqCst);
+        let _ = TcpStream::connect(self.address);
+    }
+}
+
+/// Return the key set containing the keys of all published issuers.
+fn published(state: &ServerState) -> JWKS {
+    state.issuers.iter().flat_map(|issuer| issuer.jwks().keys().to_vec()).collect()
+}
+
+/// Read a request from a connection and answer it with the key set.
+fn respond(mut stream: TcpStream, state: &Mutex<ServerState>) -> std::io::Result<()> {
+    let mut request = vec![];
+    let mut buf = [0; 1024];
+
+    while !request.ends_with(b"\r\n\r\n") {
+        let read = stream.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..read]);
+    }
+
+    let response = {
+        let mut state = state.lock().unwrap();
+        state.requests += 1;
+
+        let body = match state.status {
+            200 => serde_json::to_string(&published(&state)).unwrap_or_default(),
+            _ => String::new(),
+        };
+
+        let mut response = format!("HTTP/1.1 {} Mock\r\nConnection: close\r\nContent-Type: application/json\r\nContent-Length: {}\r\n",
+                                   state.status, body.len());
+        if let Some(ref cache_control) = state.cache_control {
+            response.push_str(&format!("Cache-Control: {}\r\n", cache_control));
+        }
+
+        response.push_str("\r\n");
+        response.push_str(&body);
+        response
+    };
+
+    stream.write_all(response.as_bytes())
+}
@@ -0,0 +1,184 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Implements a bounded cache of validated tokens, which allows
+//! skipping signature verification for tokens that clients send
+//! repeatedly.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::{JWTResult, ValidJWT};
+
+/// Cache keys are SHA-256 hashes of the tokens, which bounds the
+/// memory used by each entry regardless of the token size.
+type TokenHash = [u8; 32];
+
+struct CachedToken {
+    jwt: ValidJWT,
+    exp: SystemTime,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct Entries {
+    tokens: HashMap<TokenHash, CachedToken>,
+
+    /// Token hashes ordered by their last use, oldest first.
+    usage: BTreeMap<u64, TokenHash>,
+
+    /// Counter used for ordering uses of the cache.
+    uses: u64,
+}
+
+/// Cache of previously validated tokens, which returns the previous
+/// validation result for a token until the token expires.
+///
+/// The cache holds up to a fixed number of tokens and evicts the
+/// least recently used token when it is full. Only tokens with an
+/// "exp"-claim are cached.
+///
+/// Cached tokens are not validated again, so a cache must only be
+/// used with a single set of validations (e.g. through
+/// `Validator::token_cache`) and is not suitable for validations
+/// whose result changes over time, apart from the expiry of the
+/// token.
+///
+/// ```rust
+/// # use alcoholic_jwt::{JWK, TokenCache, ValidationError, validate};
+/// # fn example(token: &str, jwk: &JWK) -> Result<(), ValidationError> {
+/// let cache = TokenCache::new(1000);
+/// let jwt = cache.validate(token, |token| validate(token, jwk, vec![]))?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct TokenCache {
+    capacity: usize,
+    entries: Mutex<Entries>,
+}
+
+impl TokenCache {
+    /// Create an empty cache holding up to `capacity` tokens.
+    pub fn new(capacity: usize) -> TokenCache {
+        TokenCache {
+            capacity,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    /// Return the cached validation result for a token, if it has not
+    /// expired yet.
+    pub fn get(&self, token: &str) -> Option<ValidJWT> {
+        self.get_at(token, SystemTime::now())
+    }
+
+    /// Add a validated token to the cache. Tokens without an
+    /// "exp"-claim are ignored.
+    pub fn insert(&self, token: &str, jwt: &ValidJWT) {
+        let exp = match jwt.expires_at() {
+            Some(exp) => exp,
+            None => return,
+        };
+
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        let hash = openssl::sha::sha256(token.as_bytes());
+        entries.remove(&hash);
+
+        while entries.tokens.len() >= self.capacity {
+            let oldest = match entries.usage.first_key_value() {
+                Some((_, hash)) => *hash,
+                None => break,
+            };
+            entries.remove(&oldest);
+        }
+
+        let last_used = entries.touch(hash);
+        entries.tokens.insert(hash, CachedToken { jwt: jwt.clone(), exp, last_used });
+    }
+
+    /// Return the cached validation result for a token, or validate
+    /// it using the supplied function and cache the result if it is
+    /// successful.
+    pub fn validate<F>(&self, token: &str, validate: F) -> JWTResult<ValidJWT>
+    where F: FnOnce(&str) -> JWTResult<ValidJWT> {
+        if let Some(jwt) = self.get(token) {
+            return Ok(jwt);
+        }
+
+        let jwt = validate(token)?;
+        self.insert(token, &jwt);
+        Ok(jwt)
+    }
+
+    /// Return the number of tokens currently cached, including
+    /// expired tokens that have not been evicted yet.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().tokens.len()
+    }
+
+    /// Check whether no tokens are currently cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove all tokens from the cache.
+    pub fn clear(&self) {
+        *self.entries.lock().unwrap() = Entries::default();
+    }
+
+    /// Return the cached validation result for a token, if it has not
+    /// expired at the specified time.
+    pub(crate) fn get_at(&self, token: &str, now: SystemTime) -> Option<ValidJWT> {
+        let mut entries = self.entries.lock().unwrap();
+        let hash = openssl::sha::sha256(token.as_bytes());
+
+        let cached = entries.tokens.get(&hash)?;
+        if cached.exp <= now {
+            entries.remove(&hash);
+            return None;
+        }
+
+        let jwt = cached.jwt.clone();
+        let previous = cached.last_used;
+        let last_used = entries.touch(hash);
+        entries.usage.remove(&previous);
+        if let Some(cached) = entries.tokens.get_mut(&hash) {
+            cached.last_used = last_used;
+        }
+
+        Some(jwt)
+    }
+}
+
+impl Entries {
+    /// Record a use of the specified token and return its position in
+    /// the usage order.
+    fn touch(&mut self, hash: TokenHash) -> u64 {
+        self.uses += 1;
+        self.usage.insert(self.uses, hash);
+        self.uses
+    }
+
+    fn remove(&mut self, hash: &TokenHash) {
+        if let Some(cached) = self.tokens.remove(hash) {
+            self.usage.remove(&cached.last_used);
+        }
+    }
+}
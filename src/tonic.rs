@@ -0,0 +1,123 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+//! Integration with [tonic](https://github.com/hyperium/tonic), which
+//! is available if the `tonic` feature of this library is enabled.
+//!
+//! gRPC services can validate the bearer token in the `authorization`
+//! metadata of requests using the `TokenInterceptor`, which validates
+//! tokens synchronously, or the layer returned by `require_token`,
+//! which validates tokens asynchronously (e.g. for fetching key sets
+//! from a `KeyStore` without blocking). Both make the validated token
+//! (`ValidJWT`) available in the extensions of the request:
+//!
+//! ```rust,no_run
+//! # use alcoholic_jwt::tonic::TokenInterceptor;
+//! # use alcoholic_jwt::{ValidJWT, Validation, Validator};
+//! # use std::sync::Arc;
+//! # fn example(request: tonic::Request<()>) -> Result<(), tonic::Status> {
+//! # let validator = Arc::new(Validator::new(vec![Validation::NotExpired]));
+//! let interceptor = TokenInterceptor::new(validator);
+//! // ... e.g. GreeterServer::with_interceptor(greeter, interceptor)
+//!
+//! // ... in a service method:
+//! let jwt = request.extensions().get::<ValidJWT>();
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Requests without a valid token are rejected with the status returned
+//! by `status`.
+
+use ::tonic::metadata::{AsciiMetadataValue, MetadataMap};
+use ::tonic::service::Interceptor;
+use ::tonic::{Code, Request, Status};
+use std::sync::Arc;
+
+use crate::auth::rejection;
+use crate::tower::RequireToken;
+use crate::{bearer_token, AuthError, Validator};
+
+/// Convert the reason for rejecting a request into a gRPC status.
+///
+/// Missing and invalid tokens are `UNAUTHENTICATED`, tokens lacking a
+/// required scope are `PERMISSION_DENIED`. The message of the status
+/// describes the failure, and its `www-authenticate` metadata contains
+/// the challenge specified by RFC 6750.
+pub fn status(err: &AuthError) -> Status {
+    let code = match err.status() {
+        401 => Code::Unauthenticated,
+        403 => Code::PermissionDenied,
+        _ => Code::Internal,
+    };
+
+    let mut metadata = MetadataMap::new();
+    if let Some(challenge) = err.www_authenticate().and_then(|c| c.parse::<AsciiMetadataValue>().ok()) {
+        metadata.insert("www-authenticate", challenge);
+    }
+
+    Status::with_metadata(code, err.to_string(), metadata)
+}
+
+/// Interceptor that validates the bearer token of each request using a
+/// shared `Validator` and inserts the validated token into the
+/// extensions of the request.
+///
+/// Interceptors are synchronous, so tokens are validated using
+/// `Validator::validate`. Use `require_token` instead if the validator
+/// fetches its keys over HTTP.
+#[derive(Clone)]
+pub struct TokenInterceptor {
+    validator: Arc<Validator>,
+}
+
+impl TokenInterceptor {
+    /// Create an interceptor that validates tokens using the specified
+    /// validator.
+    pub fn new(validator: Arc<Validator>) -> TokenInterceptor {
+        TokenInterceptor { validator }
+    }
+}
+
+impl Interceptor for TokenInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let result = request.metadata().get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(bearer_token)
+            .ok_or(AuthError::MissingToken)
+            .and_then(|token| self.validator.validate(token).map_err(rejection));
+
+        match result {
+            Ok(jwt) => {
+                request.extensions_mut().insert(jwt);
+                Ok(request)
+            },
+            Err(err) => Err(status(&err)),
+        }
+    }
+}
+
+/// Create a layer that validates the bearer token of each request
+/// asynchronously using the specified validator, and inserts the
+/// validated token into the extensions of the request.
+///
+/// This is the asynchronous equivalent of `TokenInterceptor`, which is
+/// added to a server using `Server::layer`. Scopes can be required as
+/// with any other `tower::RequireToken` layer. Rejected requests receive
+/// the gRPC status returned by `status`.
+pub fn require_token(validator: Arc<Validator>) -> RequireToken {
+    RequireToken::new(validator).grpc()
+}
@@ -0,0 +1,181 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+//! Middleware for HTTP services based on
+//! [tower](https://github.com/tower-rs/tower) (e.g. hyper, axum or
+//! tonic), which is available if the `tower` feature of this library
+//! is enabled.
+//!
+//! The `RequireToken` layer validates the bearer token of each request
+//! using a shared `Validator`, optionally requiring specific scopes,
+//! and inserts the validated token (`ValidJWT`) into the extensions of
+//! the request:
+//!
+//! ```rust,no_run
+//! # use alcoholic_jwt::tower::RequireToken;
+//! # use alcoholic_jwt::{ValidJWT, Validation, Validator};
+//! # use std::convert::Infallible;
+//! # use std::sync::Arc;
+//! # use tower::{service_fn, Layer};
+//! # let validator = Arc::new(Validator::new(vec![Validation::NotExpired]));
+//! let service = RequireToken::new(validator).scope("read").layer(
+//!     service_fn(|req: http::Request<String>| async move {
+//!         let jwt = req.extensions().get::<ValidJWT>().unwrap();
+//!         Ok::<_, Infallible>(http::Response::new(jwt.claims.to_string()))
+//!     }),
+//! );
+//! ```
+//!
+//! Rejected requests receive an empty response with the status and
+//! `WWW-Authenticate` header described by `AuthError`, without being
+//! passed to the inner service.
+
+use http::header::{HeaderValue, AUTHORIZATION, WWW_AUTHENTICATE};
+use http::{Request, Response, StatusCode};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::auth::{authenticate, check_scopes};
+use crate::{AuthError, ValidJWT, Validator};
+
+/// Layer that rejects requests without a valid bearer token and
+/// inserts the validated token into the extensions of the request.
+#[derive(Clone)]
+pub struct RequireToken {
+    validator: Arc<Validator>,
+    scopes: Arc<[String]>,
+    rejection: Rejection,
+}
+
+/// Protocol of the responses to rejected requests.
+#[derive(Clone, Copy)]
+enum Rejection {
+    /// HTTP responses with the status described by `AuthError`.
+    Http,
+
+    /// gRPC responses with the status returned by `tonic::status`.
+    #[cfg(feature = "tonic")]
+    Grpc,
+}
+
+impl RequireToken {
+    /// Create a layer that validates tokens using the specified
+    /// validator.
+    pub fn new(validator: Arc<Validator>) -> RequireToken {
+        RequireToken { validator, scopes: Arc::new([]), rejection: Rejection::Http }
+    }
+
+    /// Reject requests with gRPC responses instead of HTTP responses.
+    #[cfg(feature = "tonic")]
+    pub(crate) fn grpc(mut self) -> RequireToken {
+        self.rejection = Rejection::Grpc;
+        self
+    }
+
+    /// Construct the response to a rejected request.
+    fn reject<B: Default>(&self, err: &AuthError) -> Response<B> {
+        match self.rejection {
+            Rejection::Http => error_response(err),
+
+            #[cfg(feature = "tonic")]
+            Rejection::Grpc => crate::tonic::status(err).into_http(),
+        }
+    }
+
+    /// Require tokens to grant the specified scope, in addition to any
+    /// previously required scopes. Tokens lacking a required scope are
+    /// rejected with `403 Forbidden`.
+    pub fn scope<S: Into<String>>(mut self, scope: S) -> RequireToken {
+        let mut scopes = self.scopes.to_vec();
+        scopes.push(scope.into());
+        self.scopes = scopes.into();
+        self
+    }
+}
+
+impl<S> Layer<S> for RequireToken {
+    type Service = RequireTokenService<S>;
+
+    fn layer(&self, inner: S) -> RequireTokenService<S> {
+        RequireTokenService { inner, layer: self.clone() }
+    }
+}
+
+/// Service constructed by the `RequireToken` layer.
+#[derive(Clone)]
+pub struct RequireTokenService<S> {
+    inner: S,
+    layer: RequireToken,
+}
+
+/// Construct the HTTP response to a rejected request.
+fn error_response<B: Default>(err: &AuthError) -> Response<B> {
+    let mut response = Response::new(B::default());
+    *response.status_mut() = StatusCode::from_u16(err.status()).unwrap_or(StatusCode::UNAUTHORIZED);
+
+    if let Some(challenge) = err.www_authenticate().and_then(|c| HeaderValue::from_str(&c).ok()) {
+        response.headers_mut().insert(WWW_AUTHENTICATE, challenge);
+    }
+
+    response
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for RequireTokenService<S>
+where S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+      S::Future: Send,
+      S::Error: Send,
+      ReqBody: Send + 'static,
+      ResBody: Default + Send + 'static {
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<ResBody>, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
+        // The service that was polled for readiness is used for this
+        // request, and replaced by a fresh clone.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let layer = self.layer.clone();
+
+        Box::pin(async move {
+            // Tokens validated by an outer layer only need to be
+            // checked for additional scopes.
+            let result = match req.extensions().get::<ValidJWT>() {
+                Some(jwt) => check_scopes(jwt, &layer.scopes).map(|_| jwt.clone()),
+                None => {
+                    let authorization = req.headers().get(AUTHORIZATION).and_then(|v| v.to_str().ok());
+                    authenticate(&layer.validator, authorization, &layer.scopes).await
+                },
+            };
+
+            match result {
+                Ok(jwt) => {
+                    req.extensions_mut().insert(jwt);
+                    inner.call(req).await
+                },
+                Err(err) => Ok(layer.reject(&err)),
+            }
+        })
+    }
+}
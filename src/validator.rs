@@ -0,0 +1,302 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Implements a reusable validator that bundles a key source, the
+//! allowed algorithms and a set of claim validations with settings
+//! shared by all of them.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::audit::audit;
+use crate::{
+    token_alg_limited, token_limits, validate_against, with_token_kid, AuditHook, Clock,
+    KeyAlgorithm, JWK, JWTResult, KeyProvider, Limits, MetricsCache, MetricsSink,
+    TokenCache, ValidJWT, Validation, ValidationError,
+};
+
+/// Reusable configuration for validating tokens, consisting of the
+/// source of keys, the allowed signature algorithms, a set of claim
+/// validations and settings that apply to all time-based validations,
+/// such as the allowed clock skew.
+///
+/// Validators are meant to be built once (e.g. at startup) and shared
+/// between request handlers. Validating a token does not copy the
+/// configured validations.
+///
+/// ```rust
+/// # use alcoholic_jwt::{JWKS, Validation, Validator};
+/// # use std::sync::Arc;
+/// # use std::time::Duration;
+/// # let jwks: JWKS = serde_json::from_str("{\"keys\":[]}").unwrap();
+/// let validator = Validator::new(vec![
+///     Validation::Issuer("auth.test.aprila.no".into()),
+///     Validation::NotExpired,
+/// ]).key_provider(Arc::new(jwks)).leeway(Duration::from_secs(30));
+///
+/// # let token = "";
+/// // ... in a request handler:
+/// let result = validator.validate(token);
+/// ```
+#[derive(Clone)]
+pub struct Validator {
+    validations: Vec<Validation>,
+    leeway: Duration,
+    limits: Limits,
+    clock: Option<Arc<dyn Clock + Send + Sync>>,
+    keys: Option<Arc<dyn KeyProvider + Send + Sync>>,
+    algorithms: Vec<KeyAlgorithm>,
+    tokens: Option<Arc<TokenCache>>,
+    metrics: Option<Arc<dyn MetricsSink>>,
+    audit: Option<Arc<dyn AuditHook>>,
+
+    /// Full set of validations applied to tokens, including the
+    /// shared settings.
+    prepared: Vec<Validation>,
+}
+
+impl Validator {
+    /// Create a validator that applies the specified claim
+    /// validations, without any leeway. Only `RS256` signatures are
+    /// allowed by default.
+    pub fn new(validations: Vec<Validation>) -> Validator {
+        Validator {
+            validations,
+            leeway: Duration::from_secs(0),
+            limits: Limits::default(),
+            clock: None,
+            keys: None,
+            algorithms: vec![KeyAlgorithm::RS256],
+            tokens: None,
+            metrics: None,
+            audit: None,
+            prepared: vec![],
+        }.prepare()
+    }
+
+    /// Set the leeway applied to all time-based validations.
+    /// `Validation::Leeway` values in the set of validations take
+    /// precedence over this setting.
+    pub fn leeway(mut self, leeway: Duration) -> Validator {
+        self.leeway = leeway;
+        self.prepare()
+    }
+
+    /// Set the clock used by all time-based validations. Defaults to
+    /// `SystemClock`.
+    pub fn clock(mut self, clock: Arc<dyn Clock + Send + Sync>) -> Validator {
+        self.clock = Some(clock);
+        self.prepare()
+    }
+
+    /// Set the limits on the size of tokens. `Validation::Limits`
+    /// values in the set of validations take precedence over this
+    /// setting.
+    pub fn limits(mut self, limits: Limits) -> Validator {
+        self.limits = limits;
+        self.prepare()
+    }
+
+    /// Set the source of keys used by `Validator::validate`, e.g. a
+    /// key set or a `KeyStore`.
+    pub fn key_provider(mut self, keys: Arc<dyn KeyProvider + Send + Sync>) -> Validator {
+        self.keys = Some(keys);
+        self
+    }
+
+    /// Set the signature algorithms that tokens may use. Tokens
+    /// specifying any other algorithm in their "alg"-header are
+    /// rejected before their signature is verified.
+    pub fn allowed_algorithms(mut self, algorithms: Vec<KeyAlgorithm>) -> Validator {
+        self.algorithms = algorithms;
+        self
+    }
+
+    /// Set a cache of validated tokens, which skips validation of
+    /// tokens that have already been validated until they expire.
+    ///
+    /// The cache must not be shared with validators using different
+    /// settings. It is not used if the validations include
+    /// `Validation::NoReplay`, as every use of a token must be
+    /// checked in that case.
+    pub fn token_cache(mut self, cache: Arc<TokenCache>) -> Validator {
+        self.tokens = Some(cache);
+        self
+    }
+
+    /// Set a sink for metrics about validated tokens and lookups in
+    /// the token cache. Key stores report their metrics separately,
+    /// see `KeyStore::metrics`.
+    pub fn metrics(mut self, metrics: Arc<dyn MetricsSink>) -> Validator {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Set a hook that is called with an audit event for every
+    /// accepted or rejected token, see `AuditHook`.
+    pub fn audit_hook(mut self, hook: Arc<dyn AuditHook>) -> Validator {
+        self.audit = Some(hook);
+        self
+    }
+
+    /// Validate a token using a key from the configured key provider.
+    ///
+    /// Returns `ValidationError::KeyNotFound` if no key provider has
+    /// been configured.
+    pub fn validate(&self, token: &str) -> JWTResult<ValidJWT> {
+        match self.keys {
+            Some(ref keys) => self.validate_with(token, keys.as_ref()),
+            None => self.record(token, no_key_provider(token, &token_limits(&self.prepared)), Instant::now()),
+        }
+    }
+
+    /// Validate a token using the specified key, see `validate`.
+    pub fn validate_with_key(&self, token: &str, jwk: &JWK) -> JWTResult<ValidJWT> {
+        let start = Instant::now();
+        let result = match self.cached(token) {
+            Some(jwt) => Ok(jwt),
+            None => self.check_algorithm(token)
+                .and_then(|_| self.store(token, validate_against(token, Some(jwk), &self.prepared))),
+        };
+
+        self.record(token, result, start)
+    }
+
+    /// Validate a token using a key from the specified key provider,
+    /// see `validate_with`.
+    pub fn validate_with<P>(&self, token: &str, provider: &P) -> JWTResult<ValidJWT>
+    where P: KeyProvider + ?Sized {
+        let start = Instant::now();
+        let result = match self.cached(token) {
+            Some(jwt) => Ok(jwt),
+            None => self.check_algorithm(token)
+                .and_then(|_| with_token_kid(token, &token_limits(&self.prepared), |kid| provider.key_for(kid)))
+                .and_then(|jwk| self.store(token, validate_against(token, Some(jwk.as_ref()), &self.prepared))),
+        };
+
+        self.record(token, result, start)
+    }
+
+    /// Asynchronous equivalent of `Validator::validate`, see
+    /// `validate_async`.
+    pub async fn validate_async(&self, token: &str) -> JWTResult<ValidJWT> {
+        let keys = match self.keys {
+            Some(ref keys) => keys,
+            None => return self.record(token, no_key_provider(token, &token_limits(&self.prepared)), Instant::now()),
+        };
+
+        let start = Instant::now();
+        let result = match self.cached(token) {
+            Some(jwt) => Ok(jwt),
+            None => self.validate_uncached_async(token, keys.as_ref()).await,
+        };
+
+        self.record(token, result, start)
+    }
+
+    /// Validate a token that is not in the token cache using a key
+    /// retrieved asynchronously from the key provider.
+    async fn validate_uncached_async(&self, token: &str, keys: &(dyn KeyProvider + Send + Sync)) -> JWTResult<ValidJWT> {
+        self.check_algorithm(token)?;
+        let kid = with_token_kid(token, &token_limits(&self.prepared), |kid| Ok(kid.map(String::from)))?;
+        let jwk = keys.key_for_async(kid.as_deref()).await?;
+        self.store(token, validate_against(token, Some(jwk.as_ref()), &self.prepared))
+    }
+
+    /// Return the full set of validations applied to tokens.
+    #[cfg(any(feature = "fetch", feature = "async"))]
+    pub(crate) fn prepared_validations(&self) -> &[Validation] {
+        &self.prepared
+    }
+
+    /// Check that the token uses one of the allowed algorithms.
+    fn check_algorithm(&self, token: &str) -> JWTResult<()> {
+        let alg = token_alg_limited(token, &token_limits(&self.prepared))?;
+        if self.algorithms.contains(&alg) {
+            Ok(())
+        } else {
+            Err(ValidationError::DisallowedAlgorithm(Some(alg.to_string())))
+        }
+    }
+
+    /// Return the token cache, unless replay detection requires every
+    /// token to be validated.
+    fn token_cache_in_use(&self) -> Option<&TokenCache> {
+        let replay = self.validations.iter().any(|v| matches!(v, Validation::NoReplay(_)));
+        self.tokens.as_deref().filter(|_| !replay)
+    }
+
+    /// Return the cached validation result for a token.
+    fn cached(&self, token: &str) -> Option<ValidJWT> {
+        let now = self.clock.as_ref().map_or_else(SystemTime::now, |clock| clock.now());
+        let jwt = self.token_cache_in_use()?.get_at(token, now);
+
+        if let Some(ref metrics) = self.metrics {
+            metrics.cache_lookup(MetricsCache::Tokens, jwt.is_some());
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(hit = jwt.is_some(), "token cache lookup");
+
+        jwt
+    }
+
+    /// Add a successful validation result to the token cache.
+    fn store(&self, token: &str, result: JWTResult<ValidJWT>) -> JWTResult<ValidJWT> {
+        if let (Some(cache), Ok(jwt)) = (self.token_cache_in_use(), &result) {
+            cache.insert(token, jwt);
+        }
+
+        result
+    }
+
+    /// Report the outcome of a validation that started at the
+    /// specified time to the metrics sink and audit hook, if any, and
+    /// record it on the active OpenTelemetry span.
+    fn record(&self, token: &str, result: JWTResult<ValidJWT>, start: Instant) -> JWTResult<ValidJWT> {
+        if let Some(ref metrics) = self.metrics {
+            metrics.validation(result.as_ref().map(|_| ()), start.elapsed());
+        }
+
+        if let Some(ref hook) = self.audit {
+            audit(hook.as_ref(), token, &result);
+        }
+
+        #[cfg(feature = "otel")]
+        crate::otel::record_validation(token, &result);
+
+        result
+    }
+
+    /// Construct the full set of validations to apply to a token.
+    fn prepare(mut self) -> Validator {
+        // Later settings take precedence, so the shared settings come
+        // first.
+        let mut validations = vec![Validation::Leeway(self.leeway), Validation::Limits(self.limits)];
+        if let Some(ref clock) = self.clock {
+            validations.push(Validation::TimeSource(clock.clone()));
+        }
+
+        validations.extend(self.validations.iter().cloned());
+        self.prepared = validations;
+        self
+    }
+}
+
+/// Return the error for validating a token without a key provider.
+fn no_key_provider(token: &str, limits: &Limits) -> JWTResult<ValidJWT> {
+    let kid = with_token_kid(token, limits, |kid| Ok(kid.map(String::from)))?;
+    Err(ValidationError::KeyNotFound(kid))
+}
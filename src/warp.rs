@@ -0,0 +1,102 @@
+// Copyright (C) 2018  Aprila Bank ASA
+//
+// alcoholic_jwt is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+//! Integration with [warp](https://github.com/seanmonstar/warp), which
+//! is available if the `warp` feature of this library is enabled.
+//!
+//! The filters of this module validate the bearer token of a request
+//! using a shared `Validator` and extract the validated token or its
+//! claims for the handler. Rejections can be turned into responses
+//! using `handle_rejection`:
+//!
+//! ```rust,no_run
+//! # use alcoholic_jwt::warp::{handle_rejection, with_claims};
+//! # use alcoholic_jwt::{Validation, Validator};
+//! # use serde_derive::Deserialize;
+//! # use std::sync::Arc;
+//! # use warp::Filter;
+//! #[derive(Deserialize)]
+//! struct User {
+//!     sub: String,
+//! }
+//!
+//! # let validator = Arc::new(Validator::new(vec![Validation::NotExpired]));
+//! let profile = warp::path("profile")
+//!     .and(with_claims(validator))
+//!     .map(|user: User| format!("Hello, {}!", user.sub))
+//!     .recover(handle_rejection);
+//! ```
+
+use ::warp::http::header::{HeaderValue, WWW_AUTHENTICATE};
+use ::warp::http::StatusCode;
+use ::warp::reject::{self, Reject, Rejection};
+use ::warp::reply::{Reply, Response};
+use ::warp::Filter;
+use serde::de::DeserializeOwned;
+use std::sync::Arc;
+
+use crate::auth::authenticate;
+use crate::{AuthError, ValidJWT, Validator};
+
+impl Reject for AuthError {}
+
+/// Create a filter that validates the bearer token of a request using
+/// the specified validator, and extracts the validated token.
+///
+/// Requests without a valid token are rejected with an `AuthError`.
+pub fn with_jwt(validator: Arc<Validator>) -> impl Filter<Extract = (ValidJWT,), Error = Rejection> + Clone {
+    ::warp::header::optional::<String>("authorization")
+        .and_then(move |authorization: Option<String>| {
+            let validator = validator.clone();
+            async move {
+                authenticate(&validator, authorization.as_deref(), &[]).await.map_err(reject::custom)
+            }
+        })
+}
+
+/// Create a filter that validates the bearer token of a request as in
+/// `with_jwt`, and extracts its claims deserialised into the specified
+/// type.
+///
+/// Tokens whose claims can not be deserialised are rejected like
+/// invalid tokens.
+pub fn with_claims<T>(validator: Arc<Validator>) -> impl Filter<Extract = (T,), Error = Rejection> + Clone
+where T: DeserializeOwned + Send + 'static {
+    with_jwt(validator).and_then(|jwt: ValidJWT| async move {
+        serde_json::from_value(jwt.claims)
+            .map_err(|err| reject::custom(AuthError::InvalidToken(err.into())))
+    })
+}
+
+/// Turn rejections caused by an `AuthError` into responses with the
+/// status and `WWW-Authenticate` header described by the error, for
+/// use with `Filter::recover`. Other rejections are passed on.
+pub async fn handle_rejection(rejection: Rejection) -> Result<Response, Rejection> {
+    let err = match rejection.find::<AuthError>() {
+        Some(err) => err,
+        None => return Err(rejection),
+    };
+
+    let mut response = StatusCode::from_u16(err.status())
+        .unwrap_or(StatusCode::UNAUTHORIZED)
+        .into_response();
+
+    if let Some(challenge) = err.www_authenticate().and_then(|c| HeaderValue::from_str(&c).ok()) {
+        response.headers_mut().insert(WWW_AUTHENTICATE, challenge);
+    }
+
+    Ok(response)
+}